@@ -0,0 +1,143 @@
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use crate::{OchenSlab, ZeroIsNone};
+
+/// Configuration for constructing an `OchenSlab`, combining options that are otherwise spread
+/// over separate constructors
+///
+/// Created by `OchenSlab::builder`. Capacity is given either as a slot count or as a byte
+/// budget, and insertion order tracking can be enabled on top of either.
+///
+/// # Example
+/// ```
+/// use ochenslab::OchenSlab;
+///
+/// let slab = OchenSlab::<u64>::builder().byte_budget(1024).ordered().build();
+/// assert_eq!(slab.capacity(), 1024 / (16 + 8 + 8));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct OchenSlabBuilder<T> {
+    capacity: Capacity,
+    ordered: bool,
+    _item: PhantomData<fn() -> T>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Capacity {
+    Slots(usize),
+    Bytes(usize),
+}
+
+impl<T> OchenSlabBuilder<T> {
+    /// Create builder for a slab with zero capacity and no insertion order tracking
+    pub fn new() -> OchenSlabBuilder<T> {
+        OchenSlabBuilder { capacity: Capacity::Slots(0), ordered: false, _item: PhantomData }
+    }
+
+    /// Set capacity to exactly `capacity` slots, like `OchenSlab::with_capacity`
+    pub fn capacity(mut self, capacity: usize) -> OchenSlabBuilder<T> {
+        self.capacity = Capacity::Slots(capacity);
+        self
+    }
+
+    /// Set capacity to as many slots as fit into `bytes`, like `OchenSlab::with_byte_budget`.
+    /// Insertion order tracking counts against the budget if enabled
+    pub fn byte_budget(mut self, bytes: usize) -> OchenSlabBuilder<T> {
+        self.capacity = Capacity::Bytes(bytes);
+        self
+    }
+
+    /// Track insertion order of items, like `OchenSlab::with_capacity_ordered`
+    pub fn ordered(mut self) -> OchenSlabBuilder<T> {
+        self.ordered = true;
+        self
+    }
+
+    /// Create slab instance with the configured options
+    pub fn build(self) -> OchenSlab<T> {
+        let capacity = self.slots();
+        if self.ordered {
+            OchenSlab::with_capacity_ordered(capacity)
+        } else {
+            OchenSlab::with_capacity(capacity)
+        }
+    }
+
+    fn slots(&self) -> usize {
+        match self.capacity {
+            Capacity::Slots(capacity) => capacity,
+            Capacity::Bytes(bytes) => {
+                let mut slot_bytes = size_of::<Option<T>>() + size_of::<usize>();
+                if self.ordered {
+                    slot_bytes += size_of::<u64>();
+                }
+                bytes / slot_bytes
+            }
+        }
+    }
+}
+
+impl<T: ZeroIsNone> OchenSlabBuilder<T> {
+    /// Create slab instance with the configured options, getting empty storage from a single
+    /// zeroed allocation like `OchenSlab::with_capacity_zeroed`
+    pub fn build_zeroed(self) -> OchenSlab<T> {
+        let capacity = self.slots();
+        let mut slab = OchenSlab::with_capacity_zeroed(capacity);
+        if self.ordered {
+            slab.inserted_at = vec![0; capacity];
+        }
+        slab
+    }
+}
+
+impl<T> Default for OchenSlabBuilder<T> {
+    fn default() -> OchenSlabBuilder<T> {
+        OchenSlabBuilder::new()
+    }
+}
+
+impl<T> OchenSlab<T> {
+    /// Create builder to configure a slab before constructing it
+    pub fn builder() -> OchenSlabBuilder<T> {
+        OchenSlabBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvictPolicy;
+
+    #[test]
+    fn builder_matches_constructors() {
+        let built = OchenSlab::<u32>::builder().capacity(5).build();
+        let direct = OchenSlab::<u32>::with_capacity(5);
+        assert_eq!(built.capacity(), direct.capacity());
+        assert_eq!(built.free_list_order().collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+
+        let built = OchenSlab::<u32>::builder().byte_budget(100).build();
+        assert_eq!(built.capacity(), OchenSlab::<u32>::with_byte_budget(100).capacity());
+
+        let built = OchenSlab::<Box<u32>>::builder().capacity(3).build_zeroed();
+        assert_eq!(built, &[None, None, None][..]);
+        assert_eq!(built.validate(), Ok(()));
+        assert_eq!(OchenSlab::<u8>::builder().build().capacity(), 0);
+    }
+
+    #[test]
+    fn builder_combines_ordered_with_other_options() {
+        let mut slab = OchenSlab::<Box<u32>>::builder().capacity(3).ordered().build_zeroed();
+        for value in 0..3 {
+            slab.insert(Box::new(value));
+        }
+        slab.remove(0);
+        slab.insert(Box::new(3));
+        assert_eq!(slab.evict_one(EvictPolicy::Newest), Some((0, Box::new(3))));
+
+        let budget = 10 * (16 + 8 + 8);
+        let slab = OchenSlab::<u64>::builder().ordered().byte_budget(budget).build();
+        assert_eq!(slab.capacity(), 10);
+        assert_eq!(OchenSlab::<u64>::builder().byte_budget(budget).build().capacity(), 13);
+    }
+}
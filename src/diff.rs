@@ -0,0 +1,283 @@
+use std::fmt;
+use std::iter::FromIterator;
+
+use crate::OchenSlab;
+
+/// Change of a single slot between two slabs
+#[derive(Debug, PartialEq, Eq)]
+pub enum SlabChange<'a, T> {
+    /// Slot is occupied only in the new slab
+    Added(&'a T),
+
+    /// Slot is occupied only in the old slab
+    Removed(&'a T),
+
+    /// Slot is occupied in both slabs by different values, old one first
+    Changed(&'a T, &'a T),
+}
+
+/// Iterator over changed slots between two slabs, created by `OchenSlab::diff`
+///
+/// Yields slot indices along with their changes in ascending index order. Unchanged slots are
+/// skipped.
+pub struct SlabDiff<'a, T> {
+    old: &'a OchenSlab<T>,
+    new: &'a OchenSlab<T>,
+    index: usize,
+}
+
+impl<T: PartialEq> OchenSlab<T> {
+    /// Compute per-slot changes needed to turn this slab into `other`.
+    /// Slabs are expected to have the same capacity. If they don't, slots beyond the capacity
+    /// of one of them are treated as vacant in it.
+    pub fn diff<'a>(&'a self, other: &'a Self) -> SlabDiff<'a, T> {
+        SlabDiff {
+            old: self, new: other, index: 0
+        }
+    }
+}
+
+impl<'a, T: PartialEq> Iterator for SlabDiff<'a, T> {
+    type Item = (usize, SlabChange<'a, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.old.capacity().max(self.new.capacity());
+        while self.index < end {
+            let index = self.index;
+            self.index += 1;
+            let change = match (self.old.get(index), self.new.get(index)) {
+                (None, Some(new)) => SlabChange::Added(new),
+                (Some(old), None) => SlabChange::Removed(old),
+                (Some(old), Some(new)) if old != new => SlabChange::Changed(old, new),
+                _ => continue,
+            };
+            return Some((index, change));
+        }
+        None
+    }
+}
+
+impl<'a, T: PartialEq + Clone> SlabDiff<'a, T> {
+    /// Collect remaining changes into an owned patch that can be applied to the old slab
+    pub fn to_patch(self) -> Patch<T> {
+        self.map(|(index, change)| {
+            let change = match change {
+                SlabChange::Added(new) => PatchChange::Added(new.clone()),
+                SlabChange::Removed(_) => PatchChange::Removed,
+                SlabChange::Changed(_, new) => PatchChange::Changed(new.clone()),
+            };
+            (index, change)
+        }).collect()
+    }
+}
+
+/// Owned change of a single slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchChange<T> {
+    /// Put a value into a vacant slot
+    Added(T),
+
+    /// Remove a value from an occupied slot
+    Removed,
+
+    /// Replace a value in an occupied slot
+    Changed(T),
+}
+
+/// Set of per-slot changes that can be applied to a slab with `OchenSlab::apply_patch`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch<T> {
+    changes: Vec<(usize, PatchChange<T>)>,
+}
+
+impl<T> Patch<T> {
+    /// Create an empty patch
+    pub fn new() -> Patch<T> {
+        Patch {
+            changes: Vec::new()
+        }
+    }
+
+    /// Add a change of slot at given index
+    pub fn push(&mut self, index: usize, change: PatchChange<T>) {
+        self.changes.push((index, change));
+    }
+
+    /// Return changes in this patch along with their indices
+    pub fn changes(&self) -> &[(usize, PatchChange<T>)] {
+        &self.changes
+    }
+}
+
+impl<T> Default for Patch<T> {
+    fn default() -> Self {
+        Patch::new()
+    }
+}
+
+impl<T> FromIterator<(usize, PatchChange<T>)> for Patch<T> {
+    fn from_iter<I: IntoIterator<Item = (usize, PatchChange<T>)>>(iter: I) -> Self {
+        Patch {
+            changes: iter.into_iter().collect()
+        }
+    }
+}
+
+/// Reason a patch couldn't be applied, referring to the offending slot index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchError {
+    /// Slot to add a value into is already occupied
+    Occupied(usize),
+
+    /// Slot to remove or replace a value in is vacant
+    Vacant(usize),
+
+    /// Slot is beyond slab capacity
+    OutOfRange(usize),
+
+    /// Slot is changed more than once by the same patch
+    Duplicate(usize),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Occupied(index) => write!(f, "slot {} is already occupied", index),
+            PatchError::Vacant(index) => write!(f, "slot {} is vacant", index),
+            PatchError::OutOfRange(index) => write!(f, "slot {} is out of range", index),
+            PatchError::Duplicate(index) => write!(f, "slot {} is changed more than once", index),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl<T> OchenSlab<T> {
+    /// Apply changes from a patch, e.g. one produced by `diff`.
+    /// The whole patch is validated first: added slots must be vacant, removed and changed ones
+    /// occupied, and every slot may appear only once. On error nothing is changed.
+    /// Revision is bumped if the patch is not empty
+    pub fn apply_patch(&mut self, patch: Patch<T>) -> Result<(), PatchError> {
+        let mut touched = vec![false; self.capacity()];
+        for (index, change) in patch.changes.iter() {
+            let index = *index;
+            let slot = self.storage.get(index).ok_or(PatchError::OutOfRange(index))?;
+            if std::mem::replace(&mut touched[index], true) {
+                return Err(PatchError::Duplicate(index));
+            }
+            match (change, slot) {
+                (PatchChange::Added(_), Some(_)) => return Err(PatchError::Occupied(index)),
+                (PatchChange::Removed, None) | (PatchChange::Changed(_), None) => {
+                    return Err(PatchError::Vacant(index))
+                }
+                _ => {}
+            }
+        }
+
+        for (index, change) in patch.changes {
+            match change {
+                PatchChange::Added(value) => {
+                    self.storage[index] = Some(value);
+                    self.free.retain(|free| *free != index);
+                    if let Some(inserted_at) = self.inserted_at.get_mut(index) {
+                        *inserted_at = self.revision.wrapping_add(1);
+                    }
+                }
+                PatchChange::Removed => {
+                    self.storage[index] = None;
+                    self.free.push(index);
+                }
+                PatchChange::Changed(value) => self.storage[index] = Some(value),
+            }
+            self.revision = self.revision.wrapping_add(1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_reports_every_kind_of_change() {
+        let mut old = OchenSlab::<usize>::with_capacity(4);
+        for value in 0..3 {
+            old.insert(value).expect("insert() failed");
+        }
+        let mut new = OchenSlab::<usize>::with_capacity(4);
+        for value in [0, 5, 2, 3] {
+            new.insert(value).expect("insert() failed");
+        }
+        new.remove(2);
+        let diff: Vec<_> = old.diff(&new).collect();
+        assert_eq!(diff, [
+            (1, SlabChange::Changed(&1, &5)),
+            (2, SlabChange::Removed(&2)),
+            (3, SlabChange::Added(&3)),
+        ]);
+        assert_eq!(old.diff(&old).count(), 0);
+    }
+
+    #[test]
+    fn diff_treats_missing_slots_as_vacant() {
+        let mut old = OchenSlab::<usize>::with_capacity(1);
+        old.insert(0).expect("insert() failed");
+        let mut new = OchenSlab::<usize>::with_capacity(2);
+        new.insert(0).expect("insert() failed");
+        new.insert(1).expect("insert() failed");
+        let diff: Vec<_> = old.diff(&new).collect();
+        assert_eq!(diff, [(1, SlabChange::Added(&1))]);
+    }
+
+    #[test]
+    fn can_apply_diff_as_patch() {
+        let mut old = OchenSlab::<usize>::with_capacity(4);
+        for value in 0..3 {
+            old.insert(value).expect("insert() failed");
+        }
+        let mut new = OchenSlab::<usize>::with_capacity(4);
+        for value in [0, 5, 2, 3] {
+            new.insert(value).expect("insert() failed");
+        }
+        new.remove(2);
+        let patch = old.diff(&new).to_patch();
+        old.apply_patch(patch).expect("apply_patch() failed");
+        assert_eq!(old.diff(&new).count(), 0);
+        assert_eq!(old.len(), 3);
+        assert_eq!(old.insert(7), Some(2));
+        assert!(old.insert(8).is_none());
+    }
+
+    #[test]
+    fn conflicting_patch_is_rejected_without_changes() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        slab.insert(0).expect("insert() failed");
+        slab.insert(1).expect("insert() failed");
+        let before = slab.to_option_vec();
+
+        let patch: Patch<usize> = vec![
+            (0, PatchChange::Removed),
+            (3, PatchChange::Added(3)),
+            (1, PatchChange::Added(4)),
+        ].into_iter().collect();
+        assert_eq!(slab.apply_patch(patch), Err(PatchError::Occupied(1)));
+
+        let mut patch = Patch::new();
+        patch.push(1, PatchChange::Changed(5));
+        patch.push(2, PatchChange::Removed);
+        assert_eq!(slab.apply_patch(patch), Err(PatchError::Vacant(2)));
+
+        let mut patch = Patch::new();
+        patch.push(2, PatchChange::Added(5));
+        patch.push(2, PatchChange::Added(6));
+        assert_eq!(slab.apply_patch(patch), Err(PatchError::Duplicate(2)));
+
+        let mut patch = Patch::new();
+        patch.push(4, PatchChange::Added(5));
+        assert_eq!(slab.apply_patch(patch), Err(PatchError::OutOfRange(4)));
+
+        assert_eq!(slab.to_option_vec(), before);
+        assert_eq!(slab.len(), 2);
+    }
+}
@@ -0,0 +1,312 @@
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ptr::{self, NonNull};
+
+/// Alignment of every slot in [`OchenDynSlab`]. Types with stricter alignment are rejected.
+pub const SLOT_ALIGN: usize = 16;
+
+// Inline storage for a single value, aligned to SLOT_ALIGN
+#[repr(C, align(16))]
+struct Slot<const SLOT_BYTES: usize>([MaybeUninit<u8>; SLOT_BYTES]);
+
+/// Limited size preallocated slab of trait objects, stored inline without boxing
+///
+/// Every slot is `SLOT_BYTES` large and aligned to [`SLOT_ALIGN`]. Values that don't fit are
+/// rejected and handed back to the caller.
+///
+/// Unsizing a value into a trait object is not something stable Rust lets a container do
+/// generically, so `insert` takes a coercion function that is almost always just `|v| v`.
+///
+/// # Example
+/// ```
+/// use ochenslab::OchenDynSlab;
+///
+/// trait Shape {
+///     fn area(&self) -> u32;
+/// }
+///
+/// struct Square(u32);
+/// impl Shape for Square {
+///     fn area(&self) -> u32 { self.0 * self.0 }
+/// }
+///
+/// struct Rect(u32, u32);
+/// impl Shape for Rect {
+///     fn area(&self) -> u32 { self.0 * self.1 }
+/// }
+///
+/// let mut slab = OchenDynSlab::<dyn Shape, 16>::with_capacity(2);
+/// let a = slab.insert(Square(3), |v| v).ok().unwrap();
+/// let b = slab.insert(Rect(2, 5), |v| v).ok().unwrap();
+///
+/// // at this point container is at its max capacity
+/// assert!(slab.insert(Square(4), |v| v).is_err());
+///
+/// assert_eq!(slab.get(a).unwrap().area(), 9);
+/// assert_eq!(slab.get(b).unwrap().area(), 10);
+/// ```
+pub struct OchenDynSlab<Dyn: ?Sized, const SLOT_BYTES: usize> {
+    // Primary storage for item bytes. Only ever accessed through raw pointers so that pointers
+    // handed out for individual slots stay valid.
+    slots: NonNull<[Slot<SLOT_BYTES>]>,
+
+    // Pointers to occupied slots, carrying trait object metadata
+    objects: Vec<Option<NonNull<Dyn>>>,
+
+    // Storage for free indices
+    free: Vec<usize>,
+
+    _marker: PhantomData<Dyn>,
+}
+
+impl<Dyn: ?Sized, const SLOT_BYTES: usize> OchenDynSlab<Dyn, SLOT_BYTES> {
+    /// Create slab instance with given capacity
+    /// Capacity will be constant for the entire lifetime of this object and cannot increase
+    pub fn with_capacity(capacity: usize) -> Self {
+        let slots: Box<[Slot<SLOT_BYTES>]> = (0..capacity)
+            .map(|_| Slot([MaybeUninit::uninit(); SLOT_BYTES]))
+            .collect();
+        let slots = NonNull::from(Box::leak(slots));
+
+        let mut objects = Vec::with_capacity(capacity);
+        objects.resize_with(capacity, || None);
+        let free = (0..capacity).rev().collect();
+
+        OchenDynSlab {
+            slots,
+            objects,
+            free,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Return number of preallocated slots
+    pub fn capacity(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Return number of elements in this container
+    pub fn len(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+
+    /// Return true if there are no elements in this container
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get reference to an item by its index
+    pub fn get(&self, index: usize) -> Option<&Dyn> {
+        let object = (*self.objects.get(index)?)?;
+        // Safety: pointer refers to a live value in a slot owned by self
+        Some(unsafe { object.as_ref() })
+    }
+
+    /// Get mutable reference to an item by its index
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Dyn> {
+        let mut object = (*self.objects.get(index)?)?;
+        // Safety: pointer refers to a live value in a slot owned by self, and self is borrowed
+        // mutably
+        Some(unsafe { object.as_mut() })
+    }
+
+    /// Insert a new item and return its index.
+    /// `coerce` turns a reference to the concrete value into a trait object reference, and is
+    /// expected to be `|v| v`.
+    /// Returns the item back if there's no space left, if it doesn't fit into a slot, or if
+    /// `coerce` returned a reference to something other than the item.
+    pub fn insert<T, F>(&mut self, value: T, coerce: F) -> Result<usize, T>
+    where
+        F: FnOnce(&mut T) -> &mut Dyn,
+    {
+        if mem::size_of::<T>() > SLOT_BYTES || mem::align_of::<T>() > SLOT_ALIGN {
+            return Err(value);
+        }
+
+        let index = match self.free.last() {
+            Some(index) => *index,
+            None => return Err(value),
+        };
+
+        let slot = self.slot_ptr(index).cast::<T>();
+        // Safety: slot is free, large and aligned enough for T
+        let object = unsafe {
+            slot.write(value);
+            NonNull::from(coerce(&mut *slot))
+        };
+
+        if object.cast::<u8>().as_ptr() != slot.cast::<u8>() {
+            // Safety: the value was written above and nothing refers to it anymore
+            return Err(unsafe { slot.read() });
+        }
+
+        self.free.pop();
+        self.objects[index] = Some(object);
+        Ok(index)
+    }
+
+    /// Remove an item by its index, dropping it.
+    /// Returns true if there was one
+    pub fn remove(&mut self, index: usize) -> bool {
+        let object = match self.objects.get_mut(index).and_then(Option::take) {
+            Some(object) => object,
+            None => return false,
+        };
+        self.free.push(index);
+        // Safety: slot has just been marked vacant, so the value won't be accessed again
+        unsafe { ptr::drop_in_place(object.as_ptr()) };
+        true
+    }
+
+    fn slot_ptr(&self, index: usize) -> *mut u8 {
+        debug_assert!(index < self.capacity());
+        // Safety: index is within the slots allocation
+        unsafe { self.slots.cast::<Slot<SLOT_BYTES>>().as_ptr().add(index).cast() }
+    }
+}
+
+impl<Dyn: ?Sized, const SLOT_BYTES: usize> Drop for OchenDynSlab<Dyn, SLOT_BYTES> {
+    fn drop(&mut self) {
+        for object in self.objects.iter_mut().filter_map(Option::take) {
+            // Safety: every stored pointer refers to a live value that is dropped exactly once
+            unsafe { ptr::drop_in_place(object.as_ptr()) };
+        }
+        // Safety: slots were leaked from a box in with_capacity
+        drop(unsafe { Box::from_raw(self.slots.as_ptr()) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    trait Handler {
+        fn handle(&self) -> usize;
+        fn bump(&mut self);
+    }
+
+    struct Empty;
+    impl Handler for Empty {
+        fn handle(&self) -> usize {
+            0
+        }
+        fn bump(&mut self) {}
+    }
+
+    struct Small(u8);
+    impl Handler for Small {
+        fn handle(&self) -> usize {
+            self.0 as usize
+        }
+        fn bump(&mut self) {
+            self.0 += 1;
+        }
+    }
+
+    struct Exact([u64; 2]);
+    impl Handler for Exact {
+        fn handle(&self) -> usize {
+            (self.0[0] + self.0[1]) as usize
+        }
+        fn bump(&mut self) {
+            self.0[1] += 1;
+        }
+    }
+
+    struct Large([u64; 3]);
+    impl Handler for Large {
+        fn handle(&self) -> usize {
+            self.0.iter().sum::<u64>() as usize
+        }
+        fn bump(&mut self) {}
+    }
+
+    #[repr(align(32))]
+    struct OverAligned(u8);
+    impl Handler for OverAligned {
+        fn handle(&self) -> usize {
+            self.0 as usize
+        }
+        fn bump(&mut self) {}
+    }
+
+    struct Counted(Rc<Cell<usize>>);
+    impl Handler for Counted {
+        fn handle(&self) -> usize {
+            self.0.get()
+        }
+        fn bump(&mut self) {}
+    }
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn can_insert_values_of_different_sizes() {
+        let mut slab = OchenDynSlab::<dyn Handler, 16>::with_capacity(4);
+        assert_eq!(slab.capacity(), 4);
+        let empty = slab.insert(Empty, |v| v).ok().expect("insert() failed");
+        let small = slab.insert(Small(7), |v| v).ok().expect("insert() failed");
+        let exact = slab.insert(Exact([1, 2]), |v| v).ok().expect("insert() failed");
+        assert_eq!(slab.len(), 3);
+        assert_eq!(slab.get(empty).unwrap().handle(), 0);
+        assert_eq!(slab.get(small).unwrap().handle(), 7);
+        assert_eq!(slab.get(exact).unwrap().handle(), 3);
+
+        slab.get_mut(small).unwrap().bump();
+        slab.get_mut(exact).unwrap().bump();
+        assert_eq!(slab.get(small).unwrap().handle(), 8);
+        assert_eq!(slab.get(exact).unwrap().handle(), 4);
+    }
+
+    #[test]
+    fn rejects_values_that_dont_fit() {
+        let mut slab = OchenDynSlab::<dyn Handler, 16>::with_capacity(4);
+        let large = slab.insert(Large([1, 2, 3]), |v| v);
+        assert_eq!(large.err().map(|v| v.0), Some([1, 2, 3]));
+        let aligned = slab.insert(OverAligned(5), |v| v);
+        assert_eq!(aligned.err().map(|v| v.0), Some(5));
+        assert!(slab.is_empty());
+    }
+
+    #[test]
+    fn can_reach_capacity_and_back() {
+        let mut slab = OchenDynSlab::<dyn Handler, 8>::with_capacity(2);
+        let a = slab.insert(Small(1), |v| v).ok().unwrap();
+        assert!(slab.insert(Small(2), |v| v).is_ok());
+        assert_eq!(slab.insert(Small(3), |v| v).err().map(|v| v.0), Some(3));
+        assert!(slab.remove(a));
+        assert!(!slab.remove(a));
+        assert!(slab.get(a).is_none());
+        assert_eq!(slab.len(), 1);
+        let c = slab.insert(Small(4), |v| v).ok().unwrap();
+        assert_eq!(slab.get(c).unwrap().handle(), 4);
+        assert_eq!(slab.len(), 2);
+    }
+
+    #[test]
+    fn rejects_foreign_references() {
+        let mut slab = OchenDynSlab::<dyn Handler, 8>::with_capacity(2);
+        let leaked: &'static mut Empty = Box::leak(Box::new(Empty));
+        let result = slab.insert(Small(2), move |_| leaked);
+        assert_eq!(result.err().map(|v| v.0), Some(2));
+        assert!(slab.is_empty());
+    }
+
+    #[test]
+    fn drops_every_value_once() {
+        let drops = Rc::new(Cell::new(0));
+        let mut slab = OchenDynSlab::<dyn Handler, 16>::with_capacity(4);
+        let a = slab.insert(Counted(drops.clone()), |v| v).ok().unwrap();
+        slab.insert(Counted(drops.clone()), |v| v).ok().unwrap();
+        slab.insert(Small(1), |v| v).ok().unwrap();
+        assert!(slab.remove(a));
+        assert_eq!(drops.get(), 1);
+        drop(slab);
+        assert_eq!(drops.get(), 2);
+    }
+}
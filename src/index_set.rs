@@ -0,0 +1,160 @@
+use crate::OchenSlab;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Set of slot indices, e.g. occupancy of an `OchenSlab`, stored as a bit mask
+///
+/// Created by `OchenSlab::index_set`. Sets taken from slabs of different item types can be
+/// combined to find entities matching a query without touching the items themselves.
+///
+/// # Example
+/// ```
+/// use ochenslab::OchenSlab;
+///
+/// let positions = OchenSlab::from_iter_with_capacity(4, vec![(0, 0), (1, 1), (2, 2)]);
+/// let mut velocities = OchenSlab::from_iter_with_capacity(4, vec![1, 2, 3]);
+/// velocities.remove(1);
+///
+/// let moving = positions.index_set().intersection(&velocities.index_set());
+/// assert_eq!(moving.iter().collect::<Vec<_>>(), [0, 2]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IndexSet {
+    // Bit for every index, trailing words may be zero
+    words: Vec<u64>,
+}
+
+impl IndexSet {
+    /// Create empty set with room for indices below `capacity`
+    pub fn with_capacity(capacity: usize) -> IndexSet {
+        IndexSet { words: vec![0; capacity.div_ceil(WORD_BITS)] }
+    }
+
+    /// Add index to this set, growing it if needed.
+    /// Returns true if index wasn't in this set before
+    pub fn insert(&mut self, index: usize) -> bool {
+        let position = index / WORD_BITS;
+        if position >= self.words.len() {
+            self.words.resize(position + 1, 0);
+        }
+        let bit = 1 << (index % WORD_BITS);
+        let added = self.words[position] & bit == 0;
+        self.words[position] |= bit;
+        added
+    }
+
+    /// Return true if index is in this set
+    pub fn contains(&self, index: usize) -> bool {
+        self.words.get(index / WORD_BITS).is_some_and(|word| word & 1 << (index % WORD_BITS) != 0)
+    }
+
+    /// Return number of indices in this set
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Return true if there are no indices in this set
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Return set of indices that are in either set
+    pub fn union(&self, other: &IndexSet) -> IndexSet {
+        let (long, short) = if self.words.len() >= other.words.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let mut words = long.words.clone();
+        for (word, other) in words.iter_mut().zip(&short.words) {
+            *word |= other;
+        }
+        IndexSet { words }
+    }
+
+    /// Return set of indices that are in both sets
+    pub fn intersection(&self, other: &IndexSet) -> IndexSet {
+        let words = self.words.iter().zip(&other.words).map(|(a, b)| a & b).collect();
+        IndexSet { words }
+    }
+
+    /// Return set of indices that are in this set but not in `other`
+    pub fn difference(&self, other: &IndexSet) -> IndexSet {
+        let mut words = self.words.clone();
+        for (word, other) in words.iter_mut().zip(&other.words) {
+            *word &= !other;
+        }
+        IndexSet { words }
+    }
+
+    /// Iterate over indices in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(position, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(position * WORD_BITS + bit)
+            })
+        })
+    }
+}
+
+impl<T> OchenSlab<T> {
+    /// Collect indices of occupied slots into a set
+    pub fn index_set(&self) -> IndexSet {
+        let mut words = vec![0; self.capacity().div_ceil(WORD_BITS)];
+        for (index, _) in self.entries() {
+            words[index / WORD_BITS] |= 1 << (index % WORD_BITS);
+        }
+        IndexSet { words }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_intersect_three_slabs() {
+        let mut a = OchenSlab::from_iter_with_capacity(130, 0..130);
+        a.retain(|index, _| index % 2 == 0);
+        let mut b = OchenSlab::from_iter_with_capacity(100, 0..100);
+        b.retain(|index, _| index % 3 == 0);
+        let c = OchenSlab::from_iter_with_capacity(80, "x".repeat(80).chars());
+
+        let set = a.index_set().intersection(&b.index_set()).intersection(&c.index_set());
+        let expected: Vec<_> = (0..80).filter(|index| index % 6 == 0).collect();
+        assert_eq!(set.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(set.len(), expected.len());
+        assert!(set.contains(66));
+        assert!(!set.contains(67));
+        assert!(!set.contains(1000));
+    }
+
+    #[test]
+    fn can_combine_sets() {
+        let a = OchenSlab::from_iter_with_capacity(70, 0..3).index_set();
+        let mut b = OchenSlab::from_iter_with_capacity(4, 0..4);
+        b.remove(0);
+        let b = b.index_set();
+        assert_eq!(a.union(&b).iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(b.union(&a).iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(a.difference(&b).iter().collect::<Vec<_>>(), [0]);
+        assert_eq!(b.difference(&a).iter().collect::<Vec<_>>(), [3]);
+        assert!(a.difference(&a).is_empty());
+        assert!(OchenSlab::<u8>::with_capacity(0).index_set().is_empty());
+    }
+
+    #[test]
+    fn can_insert_indices() {
+        let mut set = IndexSet::with_capacity(10);
+        assert!(set.insert(3));
+        assert!(!set.insert(3));
+        assert!(set.insert(200));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [3, 200]);
+    }
+}
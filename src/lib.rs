@@ -7,7 +7,62 @@
 //!
 //! [^2]: I haven't figured out how to tell that to Rust, so unsafe is necessary.
 
-/// Limited size preallocated slab storage that won't reallocate ever
+mod builder;
+mod diff;
+mod dyn_slab;
+mod index_set;
+mod region;
+mod sparse;
+mod tracked;
+
+pub use builder::OchenSlabBuilder;
+pub use diff::{Patch, PatchChange, PatchError, SlabChange, SlabDiff};
+pub use dyn_slab::{OchenDynSlab, SLOT_ALIGN};
+pub use index_set::IndexSet;
+pub use region::{HalfSlabMut, SlabRegion};
+pub use tracked::TrackedSlab;
+
+use std::alloc::{self, Layout};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize};
+use std::ptr::NonNull;
+use std::ops::{Bound, ControlFlow, Deref, Index, RangeBounds};
+
+/// Containers that can look up items by `usize` index
+pub trait IndexLookup<T> {
+    /// Get reference to an item by its index
+    fn lookup(&self, index: usize) -> Option<&T>;
+}
+
+/// Types for which `Option<Self>` with all bytes zeroed is `None`
+///
+/// Allows `OchenSlab::with_capacity_zeroed` to get empty storage straight from zeroed memory.
+///
+/// # Safety
+/// Implementors must guarantee that an all-zero bit pattern is a valid `Option<Self>` equal to
+/// `None`. This holds for types with a zero niche that Rust guarantees the null pointer
+/// optimization for, like references, `Box`, `NonNull` and non-zero integers, but not e.g. for
+/// plain integers.
+pub unsafe trait ZeroIsNone {}
+
+unsafe impl<T> ZeroIsNone for &T {}
+unsafe impl<T> ZeroIsNone for &mut T {}
+unsafe impl<T> ZeroIsNone for Box<T> {}
+unsafe impl<T> ZeroIsNone for NonNull<T> {}
+unsafe impl ZeroIsNone for NonZeroU8 {}
+unsafe impl ZeroIsNone for NonZeroU16 {}
+unsafe impl ZeroIsNone for NonZeroU32 {}
+unsafe impl ZeroIsNone for NonZeroU64 {}
+unsafe impl ZeroIsNone for NonZeroUsize {}
+
+/// Limited size preallocated slab storage that won't reallocate on its own
+///
+/// Capacity only changes through explicit calls to `try_grow_into_spare` and
+/// `shrink_to_fit_occupied`, inserts into a full slab fail instead.
 ///
 /// # Example
 /// ```
@@ -33,24 +88,116 @@ pub struct OchenSlab<T> {
 
     // Storage for free indices
     free: Vec<usize>,
+
+    // Bumped by every successful mutation
+    revision: u64,
+
+    // Next slot to overwrite by insert_ring when full
+    ring_cursor: usize,
+
+    // Revision at which each slot was last filled, for tracking insertion order. Empty unless
+    // created by with_capacity_ordered. Only read for occupied slots, so removals leave it as is
+    // and moves carry it along with the item
+    inserted_at: Vec<u64>,
+
+    // Index to resume retain_with_budget sweep from
+    sweep_cursor: usize,
+
+    // Moving average of utilization maintained by sample_occupancy
+    occupancy_average: f32,
+
+    // Index to resume pop_any scan from, every slot at or above it was vacant when last scanned
+    pop_cursor: usize,
 }
 
 impl<T> OchenSlab<T> {
     /// Create slab instance with given capacity
-    /// Capacity will be constant for the entire lifetime of this object and cannot increase
+    /// Capacity stays constant unless changed explicitly with `try_grow_into_spare` or
+    /// `shrink_to_fit_occupied`, inserts never grow it
+    /// First `capacity` inserts into a fresh slab are guaranteed to return indices
+    /// `0, 1, .., capacity - 1` in that order.
+    /// Storage is aligned as `Option<T>`. There's no way to request stricter alignment, as the
+    /// allocation must be freed with the layout it was made with. For e.g. SIMD-friendly storage
+    /// wrap items into a `#[repr(align(N))]` type instead, which aligns every slot.
     pub fn with_capacity(capacity: usize) -> OchenSlab<T> {
-        let mut storage = Vec::<Option<T>>::with_capacity(capacity);
-        storage.resize_with(capacity, || None);
-        let mut free = Vec::<usize>::with_capacity(capacity);
-        let mut i = 0 as usize;
-        free.resize_with(capacity, || {
-            let value = capacity - 1 - i;
-            i += 1;
-            value
-        });
+        let storage = (0..capacity).map(|_| None).collect();
+        let free = (0..capacity).rev().collect();
+
+        OchenSlab::from_raw_parts(storage, free)
+    }
+
+    /// Create slab instance with given capacity that also tracks insertion order of items, as
+    /// used by `retain_recent`, `evict_one` and `iter_since`. This costs an extra `u64` per slot.
+    /// Slabs created otherwise consider items inserted in ascending index order
+    pub fn with_capacity_ordered(capacity: usize) -> OchenSlab<T> {
+        let mut slab = OchenSlab::with_capacity(capacity);
+        slab.inserted_at = vec![0; capacity];
+        slab
+    }
+
+    /// Create slab instance with as many slots as fit into `bytes`, counting storage as well as
+    /// the free list
+    pub fn with_byte_budget(bytes: usize) -> OchenSlab<T> {
+        let slot_bytes = std::mem::size_of::<Option<T>>() + std::mem::size_of::<usize>();
+        OchenSlab::with_capacity(bytes / slot_bytes)
+    }
 
+    /// Create slab instance with given capacity and fill it with items from an iterator.
+    /// At most `capacity` items are taken, pass `iter.by_ref()` to keep using the rest
+    pub fn from_iter_with_capacity<I>(capacity: usize, iter: I) -> OchenSlab<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut slab = OchenSlab::with_capacity(capacity);
+        for t in iter.into_iter().take(capacity) {
+            slab.insert(t);
+        }
+        slab
+    }
+
+    /// Create slab instance from its storage and free list, e.g. previously taken apart with
+    /// `into_raw_parts`. Capacity is the storage length. Parts are not checked, use `validate`
+    /// before relying on a slab built from untrusted data. Insertion order is not tracked
+    pub fn from_raw_parts(storage: Vec<Option<T>>, free: Vec<usize>) -> OchenSlab<T> {
         OchenSlab {
-            storage, free
+            storage, free, revision: 0, ring_cursor: 0, inserted_at: Vec::new(), sweep_cursor: 0,
+            occupancy_average: 0.0, pop_cursor: 0,
+        }
+    }
+
+    /// Create slab instance from its storage and free list like `from_raw_parts`, checking that
+    /// they are consistent first. Parts are dropped if they are not
+    pub fn try_from_raw_parts(
+        storage: Vec<Option<T>>,
+        free: Vec<usize>,
+    ) -> Result<OchenSlab<T>, Corruption> {
+        let slab = OchenSlab::from_raw_parts(storage, free);
+        slab.validate()?;
+        Ok(slab)
+    }
+
+    /// Take slab apart into its storage and free list
+    pub fn into_raw_parts(self) -> (Vec<Option<T>>, Vec<usize>) {
+        (self.storage, self.free)
+    }
+
+    /// Check internal consistency: free list must contain every vacant index exactly once and
+    /// nothing else
+    pub fn validate(&self) -> Result<(), Corruption> {
+        let mut listed = vec![false; self.capacity()];
+        for index in self.free.iter().copied() {
+            match self.storage.get(index) {
+                None => return Err(Corruption::FreeOutOfRange(index)),
+                Some(Some(_)) => return Err(Corruption::FreeOccupied(index)),
+                Some(None) => {}
+            }
+            if std::mem::replace(&mut listed[index], true) {
+                return Err(Corruption::FreeDuplicate(index));
+            }
+        }
+        match self.storage.iter().zip(listed).position(|(item, listed)| item.is_none() && !listed) {
+            Some(index) => Err(Corruption::VacantNotFree(index)),
+            None => Ok(()),
         }
     }
 
@@ -59,19 +206,187 @@ impl<T> OchenSlab<T> {
         self.storage.len()
     }
 
+    /// Return layout of the item storage allocation
+    pub fn backing_layout(&self) -> Layout {
+        Layout::array::<Option<T>>(self.capacity()).expect("storage layout overflow")
+    }
+
     /// Return number of elements in this container
     pub fn len(&self) -> usize {
         self.storage.len() - self.free.len()
     }
 
+    /// Return true if there are no elements in this container
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return true if items occupy exactly indices `0..len`, with no vacant slots in between
+    pub fn is_contiguous(&self) -> bool {
+        let len = self.len();
+        self.storage[..len].iter().all(Option::is_some)
+    }
+
+    /// Return occupancy as a bit mask with bit `i` set iff slot `i` is occupied.
+    /// Returns None if capacity is more than 64
+    pub fn occupied_mask_u64(&self) -> Option<u64> {
+        if self.capacity() > 64 {
+            return None;
+        }
+        Some(self.entries().fold(0, |mask, (index, _)| mask | 1 << index))
+    }
+
+    /// Return maximal runs of consecutive slots that are all occupied or all vacant, as
+    /// `(start, len, occupied)` in ascending index order
+    pub fn runs(&self) -> Vec<(usize, usize, bool)> {
+        let mut runs: Vec<(usize, usize, bool)> = Vec::new();
+        for (index, item) in self.storage.iter().enumerate() {
+            match runs.last_mut() {
+                Some((_, len, occupied)) if *occupied == item.is_some() => *len += 1,
+                _ => runs.push((index, 1, item.is_some())),
+            }
+        }
+        runs
+    }
+
+    /// Return fraction of occupied slots, from 0 to 1.
+    /// Zero capacity slab is considered empty
+    pub fn utilization(&self) -> f64 {
+        if self.capacity() == 0 {
+            return 0.0;
+        }
+        self.len() as f64 / self.capacity() as f64
+    }
+
+    /// Update exponential moving average of utilization with its current value and return it.
+    /// `alpha` from 0 to 1 is the weight of the current value. The average starts at zero
+    pub fn sample_occupancy(&mut self, alpha: f32) -> f32 {
+        let current = self.utilization() as f32;
+        self.occupancy_average += alpha * (current - self.occupancy_average);
+        self.occupancy_average
+    }
+
     /// Get reference to an item by its index
     pub fn get(&self, index: usize) -> Option<&T> {
         self.storage.get(index)?.as_ref()
     }
 
     /// Get mutable reference to an item by its index
+    /// Bumps revision if the item exists, as it may be changed through the reference
     pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
-        self.storage.get_mut(index)?.as_mut()
+        let item = self.storage.get_mut(index)?.as_mut()?;
+        self.revision = self.revision.wrapping_add(1);
+        Some(item)
+    }
+
+    /// Get mutable references to two distinct items by their indices, or the reason why they
+    /// can't be borrowed. Bumps revision on success
+    pub fn get2_mut_checked(
+        &mut self,
+        a: usize,
+        b: usize,
+    ) -> Result<(&mut T, &mut T), DisjointError> {
+        if a == b {
+            return Err(DisjointError::SameIndex);
+        }
+        for index in [a, b].iter().copied() {
+            match self.storage.get(index) {
+                None => return Err(DisjointError::OutOfRange(index)),
+                Some(None) => return Err(DisjointError::Vacant(index)),
+                Some(Some(_)) => {}
+            }
+        }
+
+        self.revision = self.revision.wrapping_add(1);
+        let (low, high) = self.storage.split_at_mut(a.max(b));
+        let (a_item, b_item) = if a < b {
+            (&mut low[a], &mut high[0])
+        } else {
+            (&mut high[0], &mut low[b])
+        };
+        Ok((a_item.as_mut().unwrap(), b_item.as_mut().unwrap()))
+    }
+
+    /// Get mutable references to items at all distinct `indices`, or the reason why they can't
+    /// be borrowed, naming the first offending index. Bumps revision on success
+    pub fn try_get_disjoint_mut<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> Result<[&mut T; N], DisjointError> {
+        for (position, index) in indices.iter().copied().enumerate() {
+            match self.storage.get(index) {
+                None => return Err(DisjointError::OutOfRange(index)),
+                Some(None) => return Err(DisjointError::Vacant(index)),
+                Some(Some(_)) => {}
+            }
+            if indices[..position].contains(&index) {
+                return Err(DisjointError::Duplicate(index));
+            }
+        }
+
+        self.revision = self.revision.wrapping_add(1);
+        let slots = self.storage.as_mut_ptr();
+        // Safety: indices are in bounds, occupied and distinct, so references don't overlap
+        Ok(indices.map(|index| unsafe { (*slots.add(index)).as_mut().unwrap() }))
+    }
+
+    /// Get mutable references to items at each of `indices`, skipping the ones that can't be
+    /// borrowed. Out of range and vacant indices yield None, as does every repeated occurrence
+    /// of an index after the first one. Bumps revision if any reference is returned
+    pub fn get_many_mut_lenient<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> [Option<&mut T>; N] {
+        let capacity = self.storage.len();
+        let slots = self.storage.as_mut_ptr();
+        let items = std::array::from_fn(|position| {
+            let index = indices[position];
+            if index >= capacity || indices[..position].contains(&index) {
+                return None;
+            }
+            // Safety: index is in bounds and differs from every index borrowed before it
+            unsafe { (*slots.add(index)).as_mut() }
+        });
+        if items.iter().any(Option::is_some) {
+            self.revision = self.revision.wrapping_add(1);
+        }
+        items
+    }
+
+    /// Get mutable references to items at two indices, or a single reference if they are the
+    /// same. Returns None if either index is out of range or vacant. Bumps revision on success
+    pub fn get_pair(&mut self, a: usize, b: usize) -> Option<Pair<'_, T>> {
+        if a == b {
+            self.get_mut(a).map(Pair::One)
+        } else {
+            let (a, b) = self.get2_mut_checked(a, b).ok()?;
+            Some(Pair::Two(a, b))
+        }
+    }
+
+    /// Hint the CPU to fetch slot at given index into cache ahead of access.
+    /// Does nothing for indices beyond capacity or on targets without prefetch support
+    #[inline]
+    pub fn prefetch(&self, index: usize) {
+        if let Some(slot) = self.storage.get(index) {
+            #[cfg(target_arch = "x86_64")]
+            {
+                use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+                // Safety: prefetch is only a hint and never faults
+                unsafe { _mm_prefetch::<_MM_HINT_T0>((slot as *const Option<T>).cast()) };
+            }
+            #[cfg(not(target_arch = "x86_64"))]
+            let _ = slot;
+        }
+    }
+
+    /// Return revision of this container's contents
+    /// Revision is bumped by every successful operation that can change contents, including
+    /// `get_mut`, so a cached value derived from the slab is stale iff revision has changed.
+    /// Failed operations, like insert into a full slab, leave it as is. Wrap-around after
+    /// `u64::MAX` mutations is not accounted for.
+    pub fn revision(&self) -> u64 {
+        self.revision
     }
 
     /// Insert a new item and return its index.
@@ -82,81 +397,2798 @@ impl<T> OchenSlab<T> {
     pub fn insert(&mut self, t: T) -> Option<usize> {
         let index = self.free.pop()?;
         *self.storage.get_mut(index)? = Some(t);
+        self.mark_inserted(index);
         Some(index)
     }
 
+    /// Insert items from an iterator until there's no space left.
+    /// Returns number of inserted items, and the rest of the iterator if the slab got full, which
+    /// may turn out to be empty. Returns None instead if the iterator ran out first
+    pub fn extend_reporting<I>(&mut self, iter: I) -> (usize, Option<I::IntoIter>)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut iter = iter.into_iter();
+        let mut count = 0;
+        while !self.free.is_empty() {
+            match iter.next() {
+                Some(t) => self.insert(t),
+                None => return (count, None),
+            };
+            count += 1;
+        }
+        (count, Some(iter))
+    }
+
+    /// Find the first item matching `pred` in ascending index order, or insert one made by
+    /// `make` if there's none, and return its index and mutable reference to it.
+    /// Returns None if there's no match and no space left
+    pub fn get_or_insert_by<P, F>(&mut self, mut pred: P, make: F) -> Option<(usize, &mut T)>
+    where
+        P: FnMut(&T) -> bool,
+        F: FnOnce() -> T,
+    {
+        let found = self.entries().find(|(_, item)| pred(item)).map(|(index, _)| index);
+        let index = match found {
+            Some(index) => index,
+            None => self.insert(make())?,
+        };
+        Some((index, self.get_mut(index)?))
+    }
+
+    /// Insert a new item only if `pred` accepts it, and return its index.
+    /// Returns the item back if it was rejected or there's no space left
+    pub fn insert_if<P: FnOnce(&T) -> bool>(&mut self, t: T, pred: P) -> Result<usize, T> {
+        let index = match self.free.last() {
+            Some(index) if pred(&t) => *index,
+            _ => return Err(t),
+        };
+        self.free.pop();
+        self.storage[index] = Some(t);
+        self.mark_inserted(index);
+        Ok(index)
+    }
+
+    /// Insert all items and return their indices in the same order, or insert nothing and
+    /// return the items back if there's not enough space for all of them
+    pub fn insert_all(&mut self, items: Vec<T>) -> Result<Vec<usize>, Vec<T>> {
+        if items.len() > self.free.len() {
+            return Err(items);
+        }
+        Ok(items.into_iter().map(|item| self.insert(item).unwrap()).collect())
+    }
+
+    /// Insert every item at the index it comes with, e.g. to restore sparse data.
+    /// All indices are checked first: each must be within capacity, vacant, and appear only
+    /// once. On error nothing is inserted and the offending index is reported. Items are
+    /// considered inserted in iteration order
+    pub fn insert_all_at<I>(&mut self, items: I) -> Result<(), InsertAtError>
+    where
+        I: IntoIterator<Item = (usize, T)>,
+    {
+        let items: Vec<_> = items.into_iter().collect();
+        let mut placed = vec![false; self.capacity()];
+        for (index, _) in items.iter() {
+            let index = *index;
+            match self.storage.get(index) {
+                None => return Err(InsertAtError::OutOfRange(index)),
+                Some(Some(_)) => return Err(InsertAtError::Occupied(index)),
+                Some(None) => {}
+            }
+            if std::mem::replace(&mut placed[index], true) {
+                return Err(InsertAtError::Duplicate(index));
+            }
+        }
+
+        self.free.retain(|free| !placed[*free]);
+        for (index, item) in items {
+            self.storage[index] = Some(item);
+            self.mark_inserted(index);
+        }
+        Ok(())
+    }
+
+    // Bump revision and record it as insertion time of the item at index
+    fn mark_inserted(&mut self, index: usize) {
+        self.revision = self.revision.wrapping_add(1);
+        if let Some(inserted_at) = self.inserted_at.get_mut(index) {
+            *inserted_at = self.revision;
+        }
+    }
+
+    // Revision at which the item at index was inserted, or zero if insertion order isn't tracked
+    fn inserted_at(&self, index: usize) -> u64 {
+        self.inserted_at.get(index).copied().unwrap_or(0)
+    }
+
+    // Swap insertion revisions of two slots along with their items
+    fn swap_inserted_at(&mut self, a: usize, b: usize) {
+        if !self.inserted_at.is_empty() {
+            self.inserted_at.swap(a, b);
+        }
+    }
+
+    /// Insert a new item and return its index, never failing.
+    /// If there's no space left, overwrites the item at a round-robin cursor that cycles through
+    /// all slots, and returns the evicted item too.
+    /// Panics if capacity is zero
+    pub fn insert_ring(&mut self, t: T) -> (usize, Option<T>) {
+        if let Some(index) = self.free.pop() {
+            self.storage[index] = Some(t);
+            self.mark_inserted(index);
+            return (index, None);
+        }
+
+        assert!(self.capacity() > 0, "insert_ring() into a zero capacity slab");
+        let index = self.ring_cursor;
+        self.ring_cursor = (index + 1) % self.capacity();
+        let evicted = self.storage[index].replace(t);
+        self.mark_inserted(index);
+        (index, evicted)
+    }
+
+    /// Exchange contents of this slab with `other` without moving any items, e.g. for double
+    /// buffering. Indices of items stay the same. Bumps revision of both slabs.
+    /// Panics if capacities differ
+    pub fn swap_contents(&mut self, other: &mut OchenSlab<T>) {
+        assert_eq!(self.capacity(), other.capacity(), "slab capacities differ");
+        std::mem::swap(self, other);
+        // Keep both revisions ahead of insertion revisions they now hold
+        let revision = self.revision.max(other.revision).wrapping_add(1);
+        self.revision = revision;
+        other.revision = revision;
+    }
+
     /// Remove an item by its index.
     /// Returns the item by value if there was one
     pub fn remove(&mut self, index: usize) -> Option<T> {
         let value = self.storage.get_mut(index)?.take()?;
         self.free.push(index);
+        self.revision = self.revision.wrapping_add(1);
         Some(value)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Remove some item, without any guarantee which one, e.g. to drain a work list.
+    /// Returns the item along with its index, or None if the slab is empty
+    pub fn pop_any(&mut self) -> Option<(usize, T)> {
+        if self.is_empty() {
+            return None;
+        }
+        // Scan down from the cursor so that draining takes a single pass over the storage, and
+        // only wrap around to the top if items were inserted above the cursor meanwhile
+        let below = self.pop_cursor.min(self.storage.len());
+        let index = match self.storage[..below].iter().rposition(Option::is_some) {
+            Some(index) => index,
+            None => below + self.storage[below..].iter().rposition(Option::is_some)?,
+        };
+        self.pop_cursor = index;
+        Some((index, self.remove(index)?))
+    }
 
-    #[test]
-    fn can_insert_and_remove_an_element() {
-        let mut slab = OchenSlab::<usize>::with_capacity(8);
-        assert_eq!(slab.capacity(), 8);
-        let index = slab.insert(31337);
-        assert!(index.is_some());
-        assert_eq!(slab.len(), 1);
-        let index = index.unwrap();
-        let item = slab.get(index);
-        assert!(item.is_some());
-        let item = item.unwrap();
-        assert_eq!(*item, 31337);
-        slab.remove(index);
-        assert!(slab.get(index).is_none());
-        assert_eq!(slab.len(), 0);
+    /// Call `f` with mutable reference to every item in ascending index order.
+    /// Bumps revision if there are any items
+    pub fn map_in_place<F: FnMut(&mut T)>(&mut self, f: F) {
+        if !self.is_empty() {
+            self.revision = self.revision.wrapping_add(1);
+        }
+        self.storage.iter_mut().flatten().for_each(f);
     }
 
-    #[test]
-    fn can_reach_capacity() {
-        let mut slab = OchenSlab::<usize>::with_capacity(4);
-        assert_eq!(slab.capacity(), 4);
-        assert!(slab.insert(1).is_some());
-        assert!(slab.insert(2).is_some());
-        assert!(slab.insert(3).is_some());
-        assert!(slab.insert(4).is_some());
-        assert_eq!(slab.len(), 4);
-        assert!(slab.insert(5).is_none());
+    /// Remove the item with the highest index, e.g. to use this slab as a bounded stack.
+    /// Returns the item along with its index, or None if the slab is empty
+    pub fn pop_last(&mut self) -> Option<(usize, T)> {
+        let index = self.storage.iter().rposition(Option::is_some)?;
+        Some((index, self.remove(index)?))
     }
 
-    #[test]
-    fn can_reach_capacity_and_back() {
-        let mut slab = OchenSlab::<usize>::with_capacity(4);
-        assert_eq!(slab.capacity(), 4);
-        assert!(slab.insert(1).is_some());
-        let index = slab.insert(2);
-        assert!(index.is_some());
-        assert!(slab.insert(3).is_some());
-        assert!(slab.insert(4).is_some());
-        assert!(slab.insert(5).is_none());
-        assert_eq!(slab.len(), 4);
-        let item = slab.remove(index.unwrap());
-        assert_eq!(slab.len(), 3);
-        assert!(item.is_some());
-        let item = item.unwrap();
-        assert_eq!(item, 2);
-        assert!(slab.insert(6).is_some());
-        assert_eq!(slab.len(), 4);
-        assert!(slab.insert(7).is_none());
-        assert_eq!(slab.len(), 4);
+    /// Remove an item by its index, computing a value from it with `f` right before removal,
+    /// e.g. to read a link to the next item.
+    /// Returns the item by value along with the computed value if there was one
+    pub fn remove_map<R, F: FnOnce(&T) -> R>(&mut self, index: usize, f: F) -> Option<(T, R)> {
+        let result = f(self.get(index)?);
+        Some((self.remove(index)?, result))
     }
 
-    #[test]
-    fn can_mutate_element() {
-        let mut slab = OchenSlab::<usize>::with_capacity(4);
-        assert_eq!(slab.capacity(), 4);
-        let index = slab.insert(1).expect("insert() failed");
-        let item = slab.get_mut(index).expect("get_mut() failed");
-        *item = 2;
-        let item = slab.get(index).expect("get() failed");
-        assert_eq!(*item, 2);
+    /// Remove an item by its index only if `pred` accepts it.
+    /// Returns the item by value if it was removed
+    pub fn take_if<P: FnOnce(&T) -> bool>(&mut self, index: usize, pred: P) -> Option<T> {
+        if pred(self.get(index)?) {
+            self.remove(index)
+        } else {
+            None
+        }
+    }
+
+    /// Remove all items for which `f` called with item index returns false
+    pub fn retain<F: FnMut(usize, &T) -> bool>(&mut self, f: F) {
+        self.retain_reporting(f);
+    }
+
+    /// Remove all items whose indices are not in `keep`, in any order. Indices in `keep` that
+    /// are vacant or beyond capacity are ignored. Returns removed items along with their indices
+    pub fn retain_indices<I: IntoIterator<Item = usize>>(&mut self, keep: I) -> Vec<(usize, T)> {
+        let mut kept = vec![false; self.capacity()];
+        for index in keep {
+            if let Some(kept) = kept.get_mut(index) {
+                *kept = true;
+            }
+        }
+        let mut removed = Vec::new();
+        for (index, kept) in kept.into_iter().enumerate() {
+            if !kept {
+                if let Some(item) = self.remove(index) {
+                    removed.push((index, item));
+                }
+            }
+        }
+        removed
+    }
+
+    /// Remove all items whose indices are not in `keep`, walking it alongside the slots once.
+    /// `keep` must be sorted in ascending order without duplicates, which is checked in debug
+    /// builds only
+    pub fn retain_sorted_indices(&mut self, keep: &[usize]) {
+        debug_assert!(keep.windows(2).all(|pair| pair[0] < pair[1]), "indices are not sorted");
+        let mut keep = keep.iter().copied().peekable();
+        for index in 0..self.storage.len() {
+            while keep.next_if(|kept| *kept < index).is_some() {}
+            if keep.next_if_eq(&index).is_none() {
+                self.remove(index);
+            }
+        }
+    }
+
+    /// Remove all items for which `f` called with item index returns `Continue(false)`, giving
+    /// it mutable access to every item.
+    /// Sweep stops as soon as `f` returns `Break`, leaving the current and remaining items in
+    /// place, and the break value is returned. Bumps revision if any item was visited
+    pub fn try_retain<B, F>(&mut self, mut f: F) -> ControlFlow<B>
+    where
+        F: FnMut(usize, &mut T) -> ControlFlow<B, bool>,
+    {
+        for index in 0..self.storage.len() {
+            let keep = match &mut self.storage[index] {
+                Some(item) => f(index, item),
+                None => continue,
+            };
+            self.revision = self.revision.wrapping_add(1);
+            match keep {
+                ControlFlow::Continue(true) => {}
+                ControlFlow::Continue(false) => {
+                    self.remove(index);
+                }
+                ControlFlow::Break(b) => return ControlFlow::Break(b),
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Remove at most `max_removals` items for which `f` called with item index returns false.
+    /// Sweep resumes where the previous call ran out of budget, and wraps around to the start
+    /// once it has reached the end.
+    /// Returns number of removed items, and whether the sweep has reached the end
+    pub fn retain_with_budget<F>(&mut self, max_removals: usize, mut f: F) -> (usize, bool)
+    where
+        F: FnMut(usize, &T) -> bool,
+    {
+        let mut removed = 0;
+        for index in self.sweep_cursor..self.storage.len() {
+            match &self.storage[index] {
+                Some(item) if !f(index, item) => {
+                    if removed == max_removals {
+                        self.sweep_cursor = index;
+                        return (removed, false);
+                    }
+                    self.remove(index);
+                    removed += 1;
+                }
+                _ => {}
+            }
+        }
+        self.sweep_cursor = 0;
+        (removed, true)
+    }
+
+    /// Remove all items for which `f` called with item index returns false.
+    /// Returns number of `(kept, removed)` items
+    pub fn retain_reporting<F: FnMut(usize, &T) -> bool>(&mut self, mut f: F) -> (usize, usize) {
+        let mut kept = 0;
+        let mut removed = 0;
+        for index in 0..self.storage.len() {
+            match &self.storage[index] {
+                Some(item) if f(index, item) => kept += 1,
+                Some(_) => {
+                    self.remove(index);
+                    removed += 1;
+                }
+                None => {}
+            }
+        }
+        (kept, removed)
+    }
+
+    /// Remove all items and iterate over them along with their former indices in the order
+    /// given by `cmp`.
+    /// Items are removed and sorted right away, so the slab is empty even if the iterator is
+    /// dropped early
+    pub fn drain_sorted_by<F>(&mut self, mut cmp: F) -> impl Iterator<Item = (usize, T)>
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut items: Vec<_> = self.storage.iter_mut()
+            .enumerate()
+            .filter_map(|(index, item)| Some((index, item.take()?)))
+            .collect();
+        if !items.is_empty() {
+            self.rebuild_free_list();
+            self.revision = self.revision.wrapping_add(1);
+        }
+        items.sort_by(|(_, a), (_, b)| cmp(a, b));
+        items.into_iter()
+    }
+
+    /// Remove all items except `n` most recently inserted ones, see `with_capacity_ordered`.
+    /// Returns removed items along with their indices, oldest first. Does nothing if there are
+    /// no more than `n` items
+    pub fn retain_recent(&mut self, n: usize) -> Vec<(usize, T)> {
+        if self.len() <= n {
+            return Vec::new();
+        }
+
+        let mut occupied: Vec<_> = self.storage.iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_some())
+            .map(|(index, _)| (self.inserted_at(index), index))
+            .collect();
+        occupied.sort_unstable();
+        let evicted = occupied.len() - n;
+        occupied[..evicted].iter()
+            .filter_map(|(_, index)| Some((*index, self.remove(*index)?)))
+            .collect()
+    }
+
+    /// Remove items in ascending index order while `pred` called with item index returns true.
+    /// The first item it rejects and everything after it stays in place. Returns removed items
+    /// along with their indices
+    pub fn drain_while<P: FnMut(usize, &T) -> bool>(&mut self, mut pred: P) -> Vec<(usize, T)> {
+        let mut drained = Vec::new();
+        for index in 0..self.storage.len() {
+            match &self.storage[index] {
+                Some(item) if !pred(index, item) => break,
+                Some(_) => drained.push((index, self.remove(index).unwrap())),
+                None => {}
+            }
+        }
+        drained
+    }
+
+    /// Remove all items with indices in `range` and append them along with their indices to
+    /// `out`, in ascending index order. Parts of `range` beyond capacity are ignored.
+    /// Returns number of removed items
+    pub fn drain_range_into<R>(&mut self, range: R, out: &mut Vec<(usize, T)>) -> usize
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.saturating_add(1),
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => usize::MAX,
+        };
+        let before = out.len();
+        for index in start..end.min(self.capacity()) {
+            if let Some(item) = self.remove(index) {
+                out.push((index, item));
+            }
+        }
+        out.len() - before
+    }
+
+    /// Remove a single item chosen by `policy` and return it along with its index.
+    /// Returns None if there are no items
+    pub fn evict_one(&mut self, policy: EvictPolicy) -> Option<(usize, T)> {
+        let index = {
+            let mut occupied = self.entries().map(|(index, _)| (self.inserted_at(index), index));
+            match policy {
+                EvictPolicy::Oldest => occupied.min()?.1,
+                EvictPolicy::Newest => occupied.max()?.1,
+                EvictPolicy::LowestIndex => occupied.next()?.1,
+                EvictPolicy::HighestIndex => occupied.last()?.1,
+            }
+        };
+        Some((index, self.remove(index)?))
+    }
+
+    /// Remove an item by its index and move the item with the highest index into its slot.
+    /// Returns the removed item by value if there was one, along with the old index of the
+    /// moved item, or None if no item had to be moved. Only the moved item changes its index
+    pub fn swap_remove_return_moved(&mut self, index: usize) -> Option<(T, Option<usize>)> {
+        let value = self.storage.get_mut(index)?.take()?;
+        let above = self.storage[index..].iter().rposition(Option::is_some);
+        let moved = above.map(|last| index + last);
+        let vacant = match moved {
+            Some(last) => {
+                self.storage.swap(index, last);
+                self.swap_inserted_at(index, last);
+                last
+            }
+            None => index,
+        };
+        self.free.push(vacant);
+        self.revision = self.revision.wrapping_add(1);
+        Some((value, moved))
+    }
+
+    /// Remove an item by its index, shifting all items above it one slot down.
+    /// Returns the item by value if there was one
+    /// Meant for slabs used as an ordered sequence packed at `0..len`: relative order is
+    /// preserved and indices stay dense, so the next insert lands right after the last item.
+    /// Unlike `remove` this changes indices of every item above `index`, so any indices held for
+    /// them become invalid.
+    pub fn shift_remove(&mut self, index: usize) -> Option<T> {
+        let value = self.storage.get_mut(index)?.take()?;
+        let last = self.storage.iter().rposition(Option::is_some).map_or(index, |last| {
+            last.max(index)
+        });
+        self.storage[index..=last].rotate_left(1);
+        if !self.inserted_at.is_empty() {
+            self.inserted_at[index..=last].rotate_left(1);
+        }
+        for free in self.free.iter_mut().filter(|free| **free > index && **free <= last) {
+            *free -= 1;
+        }
+        self.free.push(last);
+        self.revision = self.revision.wrapping_add(1);
+        Some(value)
+    }
+
+    /// Move all items into free slots of `dst`, leaving this slab empty.
+    /// Items are moved in ascending index order and take `dst` slots in its insert order, so a
+    /// fresh `dst` gets them packed at `0..len`. Returns `(old, new)` index pairs, or an error
+    /// without moving anything if `dst` doesn't have enough free slots
+    pub fn compact_into(
+        &mut self,
+        dst: &mut OchenSlab<T>,
+    ) -> Result<Vec<(usize, usize)>, CompactError> {
+        let available = dst.capacity() - dst.len();
+        if available < self.len() {
+            return Err(CompactError {
+                needed: self.len(), available
+            });
+        }
+
+        let mut moves = Vec::with_capacity(self.len());
+        for old in 0..self.storage.len() {
+            if let Some(item) = self.remove(old) {
+                let new = dst.insert(item).expect("compaction target is full");
+                moves.push((old, new));
+            }
+        }
+        self.rebuild_free_list();
+        Ok(moves)
+    }
+
+    /// Remove all items for which `f` called with item index returns false, and pack the rest at
+    /// `0..len` keeping their relative order, in a single pass.
+    /// Returns `(old, new)` index pairs of all remaining items in ascending order. Indices of
+    /// moved items change, so any indices held for them become invalid. Bumps revision if any
+    /// item is removed or moved
+    pub fn retain_compacting<F>(&mut self, mut f: F) -> Vec<(usize, usize)>
+    where
+        F: FnMut(usize, &mut T) -> bool,
+    {
+        let mut moves = Vec::with_capacity(self.len());
+        let mut changed = false;
+        for old in 0..self.storage.len() {
+            let keep = match &mut self.storage[old] {
+                Some(item) => f(old, item),
+                None => continue,
+            };
+            changed |= !keep;
+            if !keep {
+                self.storage[old] = None;
+                continue;
+            }
+            let new = moves.len();
+            changed |= new != old;
+            self.storage.swap(old, new);
+            self.swap_inserted_at(old, new);
+            moves.push((old, new));
+        }
+        if changed {
+            self.rebuild_free_list();
+            self.revision = self.revision.wrapping_add(1);
+        }
+        moves
+    }
+
+    /// Move a single item with the lowest index above the lowest vacant slot down into that
+    /// slot, for compacting items step by step.
+    /// Returns `(old, new)` index of the moved item, or None if items are contiguous already.
+    /// Index of the moved item changes, so any index held for it becomes invalid.
+    /// Free list is kept in descending order, so next inserts fill the lowest vacant slots first
+    pub fn relocate_step(&mut self) -> Option<(usize, usize)> {
+        let new = self.storage.iter().position(Option::is_none)?;
+        let old = new + 1 + self.storage[new + 1..].iter().position(Option::is_some)?;
+        self.storage.swap(old, new);
+        self.swap_inserted_at(old, new);
+        if let Some(free) = self.free.iter_mut().find(|free| **free == new) {
+            *free = old;
+        }
+        self.free.sort_unstable_by(|a, b| b.cmp(a));
+        self.revision = self.revision.wrapping_add(1);
+        Some((old, new))
+    }
+
+    /// Pack items at `0..len` in ascending order of their indices and reduce capacity to `len`.
+    /// Returns number of bytes released.
+    /// Unlike anything else this reallocates storage, invalidating pointers to items, and changes
+    /// indices of items. New index of an item is its position in `snapshot()` taken before
+    pub fn shrink_to_fit_occupied(&mut self) -> usize {
+        let before = self.allocated_bytes();
+        let len = self.len();
+        let occupied: Vec<_> = self.storage.iter().map(Option::is_some).collect();
+        let mut storage = Vec::with_capacity(len);
+        storage.extend(self.storage.drain(..).filter(Option::is_some));
+        if !self.inserted_at.is_empty() {
+            let mut inserted_at = Vec::with_capacity(len);
+            let kept = self.inserted_at.iter().zip(&occupied).filter(|(_, occupied)| **occupied);
+            inserted_at.extend(kept.map(|(at, _)| *at));
+            self.inserted_at = inserted_at;
+        }
+        self.storage = storage;
+        // Shrink rather than reallocate, a free list built without spare room must not grow here
+        self.free.clear();
+        self.free.shrink_to(len);
+        self.ring_cursor = 0;
+        self.sweep_cursor = 0;
+        self.revision = self.revision.wrapping_add(1);
+        before.saturating_sub(self.allocated_bytes())
+    }
+
+    /// Grow capacity into spare capacity of the storage vector, e.g. one passed to
+    /// `from_raw_parts` with room to spare, without reallocating it. New slots are taken by next
+    /// inserts in ascending index order. Returns number of slots added
+    pub fn try_grow_into_spare(&mut self) -> usize {
+        let old = self.storage.len();
+        let new = self.storage.capacity();
+        if new == old {
+            return 0;
+        }
+        self.storage.resize_with(new, || None);
+        if !self.inserted_at.is_empty() {
+            self.inserted_at.resize(new, 0);
+        }
+        self.free.extend((old..new).rev());
+        self.revision = self.revision.wrapping_add(1);
+        new - old
+    }
+
+    // Number of bytes allocated for storage and bookkeeping
+    fn allocated_bytes(&self) -> usize {
+        self.storage.capacity() * std::mem::size_of::<Option<T>>()
+            + self.free.capacity() * std::mem::size_of::<usize>()
+            + self.inserted_at.capacity() * std::mem::size_of::<u64>()
+    }
+
+    /// Consume this slab and split its items into two slabs, `(matching, non_matching)`,
+    /// according to `f` called with each item's index.
+    /// Each slab has capacity equal to its item count. Items get fresh indices, packed at
+    /// `0..len` in the order of their original indices
+    pub fn split_by<F: FnMut(usize, &T) -> bool>(self, mut f: F) -> (OchenSlab<T>, OchenSlab<T>) {
+        let (matching, non_matching): (Vec<_>, Vec<_>) = self.storage.into_iter()
+            .enumerate()
+            .filter_map(|(index, item)| Some((index, item?)))
+            .partition(|(index, item)| f(*index, item));
+        let into_slab = |items: Vec<(usize, T)>| {
+            OchenSlab::from_iter_with_capacity(items.len(), items.into_iter().map(|(_, item)| item))
+        };
+        (into_slab(matching), into_slab(non_matching))
+    }
+
+    /// Consume this slab and split it at `threshold` into two slabs, with capacities `threshold`
+    /// and `capacity - threshold`.
+    /// Items below `threshold` keep their indices, items at or above it move to
+    /// `index - threshold` in the second slab. Insertion order is preserved within each slab.
+    /// Panics if `threshold` exceeds capacity
+    pub fn split_at(mut self, threshold: usize) -> (OchenSlab<T>, OchenSlab<T>) {
+        assert!(threshold <= self.capacity(), "split index is out of range");
+        let high_storage = self.storage.split_off(threshold);
+        let high_inserted_at = if self.inserted_at.is_empty() {
+            Vec::new()
+        } else {
+            self.inserted_at.split_off(threshold)
+        };
+        // Release the high half from the low slab's allocation, so that it doesn't hold on to
+        // memory it can only reach with try_grow_into_spare
+        self.storage.shrink_to_fit();
+        self.inserted_at.shrink_to_fit();
+        let revision = self.revision;
+        let into_slab = |storage: Vec<Option<T>>, inserted_at: Vec<u64>| {
+            let mut slab = OchenSlab::from_raw_parts(storage, Vec::new());
+            slab.rebuild_free_list();
+            slab.inserted_at = inserted_at;
+            slab.revision = revision;
+            slab
+        };
+        (
+            into_slab(self.storage, self.inserted_at),
+            into_slab(high_storage, high_inserted_at),
+        )
+    }
+
+    /// Move every item from its index to `mapping(index)`.
+    /// Mapping must be injective, stay within capacity and move every item into a vacant slot or
+    /// keep it in place, otherwise nothing is moved and an error is returned. Returns
+    /// `(old, new)` index pairs in ascending order of old index. Any indices held for moved items
+    /// become invalid. Free list is rebuilt to insert into the lowest vacant index first
+    pub fn reindex<F>(&mut self, mut mapping: F) -> Result<Vec<(usize, usize)>, ReindexError>
+    where
+        F: FnMut(usize) -> usize,
+    {
+        let mut taken = vec![false; self.capacity()];
+        let mut moves = Vec::with_capacity(self.len());
+        for (old, item) in self.storage.iter().enumerate() {
+            if item.is_none() {
+                continue;
+            }
+            let new = mapping(old);
+            if new != old && self.storage.get(new).is_some_and(Option::is_some) {
+                return Err(ReindexError::Occupied { old, new });
+            }
+            match taken.get_mut(new) {
+                None => return Err(ReindexError::OutOfRange { old, new }),
+                Some(true) => return Err(ReindexError::Collision { old, new }),
+                Some(taken) => *taken = true,
+            }
+            moves.push((old, new));
+        }
+
+        let items: Vec<_> = moves.iter()
+            .map(|(old, _)| (self.storage[*old].take(), self.inserted_at(*old)))
+            .collect();
+        for ((_, new), (item, inserted_at)) in moves.iter().zip(items) {
+            self.storage[*new] = item;
+            if let Some(at) = self.inserted_at.get_mut(*new) {
+                *at = inserted_at;
+            }
+        }
+        self.rebuild_free_list();
+        self.revision = self.revision.wrapping_add(1);
+        Ok(moves)
+    }
+
+    /// Move every item from index `i` to `(i + offset) % capacity`.
+    /// Every item must land on a vacant slot or stay in place, otherwise nothing is moved and
+    /// the first item that would land on another one is reported. Returns `(old, new)` index
+    /// pairs in ascending order of old index. Free list is rebuilt like in `reindex`
+    pub fn rotate(&mut self, offset: usize) -> Result<Vec<(usize, usize)>, RotateError> {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return Ok(Vec::new());
+        }
+        let offset = offset % capacity;
+        match self.reindex(|old| (old + offset) % capacity) {
+            Ok(moves) => Ok(moves),
+            Err(ReindexError::Occupied { old, new }) => Err(RotateError { old, new }),
+            Err(err) => unreachable!("rotation is a bijection within capacity: {}", err),
+        }
+    }
+
+    /// Get mutable access to all slots for bulk changes of occupancy.
+    /// Free list is not updated, call `rebuild_free_list` after changing which slots are
+    /// occupied. Insertion order of items put into slots this way is unspecified. Bumps revision
+    pub fn as_mut_slots(&mut self) -> &mut [Option<T>] {
+        self.revision = self.revision.wrapping_add(1);
+        &mut self.storage
+    }
+
+    /// Recompute free list from current slot occupancy, so that next inserts go into vacant
+    /// slots in ascending index order.
+    /// The old free list is discarded entirely, so this also repairs a slab that fails
+    /// `validate`, e.g. one built by `from_raw_parts` with a bogus free list
+    pub fn rebuild_free_list(&mut self) {
+        self.free.clear();
+        let vacant = self.storage.iter().enumerate().rev().filter(|(_, item)| item.is_none());
+        self.free.extend(vacant.map(|(index, _)| index));
+    }
+
+    /// Lazily iterate over mutable references to items at `indices`.
+    /// Out of range and vacant indices are skipped, as are repeated occurrences of an index, so
+    /// every item is yielded at most once. Bumps revision once the first item is yielded
+    pub fn disjoint_iter_mut<I>(&mut self, indices: I) -> DisjointIterMut<'_, T, I::IntoIter>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        DisjointIterMut {
+            slots: NonNull::from(self.storage.as_mut_slice()).cast(),
+            capacity: self.storage.len(),
+            seen: IndexSet::with_capacity(self.storage.len()),
+            revision: Some(&mut self.revision),
+            indices: indices.into_iter(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get a view that addresses items by their position among occupied slots rather than by
+    /// their index, as if they were packed into a contiguous array
+    pub fn dense_view(&self) -> DenseView<'_, T> {
+        let indices = self.storage.iter()
+            .enumerate()
+            .filter_map(|(index, item)| item.as_ref().map(|_| index))
+            .collect();
+        DenseView {
+            slab: self, indices
+        }
+    }
+
+    // Iterate over items along with their indices in ascending index order
+    fn entries(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.storage.iter()
+            .enumerate()
+            .filter_map(|(index, item)| Some((index, item.as_ref()?)))
+    }
+
+    /// Iterate over raw pointers to items along with their indices in ascending index order,
+    /// e.g. to hand them over FFI.
+    /// Pointers are only valid while the slab is neither mutated, moved out of, nor dropped.
+    /// They are derived from a shared borrow, so writing through them is undefined behavior
+    pub fn iter_raw(&self) -> impl Iterator<Item = (usize, NonNull<T>)> + '_ {
+        self.entries().map(|(index, item)| (index, NonNull::from(item)))
+    }
+
+    /// Clone slot layout into a new slab with every item mapped by `f`, keeping indices, free
+    /// list and insertion order
+    pub fn clone_map<U, F: FnMut(&T) -> U>(&self, mut f: F) -> OchenSlab<U> {
+        let storage = self.storage.iter().map(|item| item.as_ref().map(&mut f)).collect();
+        let mut slab = OchenSlab::from_raw_parts(storage, self.free.clone());
+        slab.inserted_at = self.inserted_at.clone();
+        slab.revision = self.revision;
+        slab
+    }
+
+    /// Iterate over items in ascending index order along with their indices and elements of
+    /// `side` at the same indices, for metadata kept in a parallel array.
+    /// Items at indices beyond `side` are skipped
+    pub fn iter_with<'a, M>(
+        &'a self,
+        side: &'a [M],
+    ) -> impl Iterator<Item = (usize, &'a T, &'a M)> + 'a {
+        self.storage.iter()
+            .zip(side)
+            .enumerate()
+            .filter_map(|(index, (item, meta))| Some((index, item.as_ref()?, meta)))
+    }
+
+    /// Iterate over free indices in the order next inserts will take them
+    pub fn free_list_order(&self) -> impl Iterator<Item = usize> + '_ {
+        self.free.iter().rev().copied()
+    }
+
+    /// Iterate over items inserted after `revision` was current, along with their indices in
+    /// ascending index order. Pass a value previously returned by `revision` to visit only items
+    /// added since then. Yields nothing unless the slab was created by `with_capacity_ordered`
+    pub fn iter_since(&self, revision: u64) -> impl Iterator<Item = (usize, &T)> {
+        self.entries().filter(move |(index, _)| self.inserted_at(*index) > revision)
+    }
+
+    /// Collect indices of all items in ascending order.
+    /// Since indices are stable, this allows iterating over the items while inserting and
+    /// removing others: an index of a removed item just yields None from `get`. Note that its
+    /// slot can be reused by an item inserted in the meantime
+    pub fn snapshot(&self) -> Vec<usize> {
+        self.entries().map(|(index, _)| index).collect()
+    }
+
+    /// Iterate over items along with their indices in batches of `chunk_size`, in ascending index
+    /// order. The last batch may be shorter.
+    /// Panics if `chunk_size` is zero
+    pub fn iter_chunks(&self, chunk_size: usize) -> impl Iterator<Item = Vec<(usize, &T)>> {
+        assert!(chunk_size > 0, "chunk size must be non-zero");
+        let mut entries = self.entries();
+        std::iter::from_fn(move || {
+            let chunk: Vec<_> = entries.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                None
+            } else {
+                Some(chunk)
+            }
+        })
+    }
+
+    /// Call `f` with every item and its index in ascending index order, and return the first
+    /// value it produces.
+    /// Returns None if `f` produced nothing for every item
+    pub fn find_map<U, F: FnMut(usize, &T) -> Option<U>>(&self, mut f: F) -> Option<U> {
+        self.entries().find_map(|(index, item)| f(index, item))
+    }
+
+    /// Count items in buckets given by `key` computed for each item
+    pub fn histogram<K, F>(&self, mut key: F) -> HashMap<K, usize>
+    where
+        K: Eq + Hash,
+        F: FnMut(&T) -> K,
+    {
+        let mut histogram = HashMap::new();
+        for item in self.storage.iter().flatten() {
+            *histogram.entry(key(item)).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Return index of the `n`-th occupied slot in ascending index order.
+    /// Returns None if there are fewer than `n + 1` items
+    pub fn nth_occupied(&self, n: usize) -> Option<usize> {
+        self.storage.iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_some())
+            .nth(n)
+            .map(|(index, _)| index)
+    }
+
+    /// Iterate over indices occupied both in this slab and in `other`, in ascending order.
+    /// Useful when several slabs hold different components of the same entities
+    pub fn intersect_indices<'a, U>(
+        &'a self,
+        other: &'a OchenSlab<U>,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.storage.iter()
+            .zip(other.storage.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a.is_some() && b.is_some())
+            .map(|(index, _)| index)
+    }
+
+    /// Run `f` with a scoped handle to this slab and return its result.
+    /// Every item inserted through the handle is removed once `f` returns or panics. Removals of
+    /// items that existed before the scope are not undone. Indices of scoped items that escape
+    /// `f` become dangling afterwards.
+    pub fn scope<R>(&mut self, f: impl FnOnce(&mut ScopedSlab<'_, T>) -> R) -> R {
+        f(&mut ScopedSlab {
+            slab: self, inserted: Vec::new()
+        })
+    }
+}
+
+/// Handle to an `OchenSlab` that removes items inserted through it when dropped
+///
+/// Created by `OchenSlab::scope`. Dereferences to the underlying slab for read-only access.
+pub struct ScopedSlab<'a, T> {
+    slab: &'a mut OchenSlab<T>,
+
+    // Indices of items inserted within this scope
+    inserted: Vec<usize>,
+}
+
+impl<'a, T> ScopedSlab<'a, T> {
+    /// Get mutable reference to an item by its index
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.slab.get_mut(index)
+    }
+
+    /// Insert a new item that will be removed at the end of the scope and return its index.
+    /// Returns None if there's no space left
+    pub fn insert(&mut self, t: T) -> Option<usize> {
+        let index = self.slab.insert(t)?;
+        self.inserted.push(index);
+        Some(index)
+    }
+
+    /// Remove an item by its index.
+    /// Returns the item by value if there was one
+    /// Removing an item that existed before the scope is permanent.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let value = self.slab.remove(index)?;
+        self.inserted.retain(|inserted| *inserted != index);
+        Some(value)
+    }
+
+    /// Run `f` with a nested scoped handle, see `OchenSlab::scope`
+    pub fn scope<R>(&mut self, f: impl FnOnce(&mut ScopedSlab<'_, T>) -> R) -> R {
+        self.slab.scope(f)
+    }
+}
+
+impl<'a, T> Deref for ScopedSlab<'a, T> {
+    type Target = OchenSlab<T>;
+
+    fn deref(&self) -> &OchenSlab<T> {
+        self.slab
+    }
+}
+
+impl<'a, T> Drop for ScopedSlab<'a, T> {
+    fn drop(&mut self) {
+        for index in self.inserted.drain(..) {
+            self.slab.remove(index);
+        }
+    }
+}
+
+impl<T: Clone> OchenSlab<T> {
+    /// Create slab instance with given capacity with every slot occupied by a clone of `value`
+    pub fn new_filled(capacity: usize, value: T) -> OchenSlab<T> {
+        OchenSlab::from_raw_parts(vec![Some(value); capacity], Vec::with_capacity(capacity))
+    }
+
+    /// Clone items matching `pred` into a new slab of the same capacity, keeping their indices
+    pub fn clone_filtered<P: FnMut(&T) -> bool>(&self, mut pred: P) -> OchenSlab<T> {
+        let storage = self.storage.iter()
+            .map(|item| item.as_ref().filter(|item| pred(item)).cloned())
+            .collect();
+        let mut slab = OchenSlab::from_raw_parts(storage, Vec::with_capacity(self.capacity()));
+        slab.rebuild_free_list();
+        slab.inserted_at = self.inserted_at.clone();
+        slab.revision = self.revision;
+        slab
+    }
+
+    /// Clone slot layout into a capacity-long vector, with None at every vacant index
+    pub fn to_option_vec(&self) -> Vec<Option<T>> {
+        self.storage.clone()
+    }
+}
+
+impl<T> OchenSlab<OchenSlab<T>> {
+    /// Iterate over items of all inner slabs along with their `(outer, inner)` indices
+    pub fn iter_nested(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        self.entries().flat_map(|(outer, slab)| {
+            slab.entries().map(move |(inner, item)| ((outer, inner), item))
+        })
+    }
+}
+
+impl<T> IndexLookup<T> for OchenSlab<T> {
+    fn lookup(&self, index: usize) -> Option<&T> {
+        self.get(index)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OchenSlab<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.entries()).finish()
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for OchenSlab<T> {
+    /// Create a full slab with capacity `N` holding array items at their array indices
+    fn from(items: [T; N]) -> OchenSlab<T> {
+        OchenSlab::from_iter_with_capacity(N, items)
+    }
+}
+
+impl<T: PartialEq> PartialEq<[Option<T>]> for OchenSlab<T> {
+    fn eq(&self, other: &[Option<T>]) -> bool {
+        self.storage[..] == *other
+    }
+}
+
+impl<'a, T: PartialEq> PartialEq<&'a [Option<T>]> for OchenSlab<T> {
+    fn eq(&self, other: &&'a [Option<T>]) -> bool {
+        self.storage[..] == **other
+    }
+}
+
+impl<T: ZeroIsNone> OchenSlab<T> {
+    /// Create slab instance with given capacity, like `with_capacity`, getting empty storage
+    /// from a single zeroed allocation instead of initializing every slot
+    pub fn with_capacity_zeroed(capacity: usize) -> OchenSlab<T> {
+        let layout = Layout::array::<Option<T>>(capacity).expect("storage layout overflow");
+        if layout.size() == 0 {
+            return OchenSlab::with_capacity(capacity);
+        }
+
+        // Safety: layout is non-zero sized, and zeroed memory is a valid None for T: ZeroIsNone
+        let storage = unsafe {
+            let ptr = alloc::alloc_zeroed(layout);
+            if ptr.is_null() {
+                alloc::handle_alloc_error(layout);
+            }
+            Vec::from_raw_parts(ptr.cast::<Option<T>>(), capacity, capacity)
+        };
+        let free = (0..capacity).rev().collect();
+        OchenSlab::from_raw_parts(storage, free)
+    }
+}
+
+impl<T: Ord> OchenSlab<T> {
+    /// Insert a new item so that items stay sorted by value in ascending index order, and return
+    /// its index.
+    /// Returns None if there's no space left
+    /// Expects items to be sorted already, e.g. if they were only inserted with this method.
+    /// Items are shifted by one slot towards the nearest vacant slot to make room, which takes
+    /// O(n) and changes their indices, so any indices held for them become invalid.
+    pub fn insert_sorted(&mut self, value: T) -> Option<usize> {
+        if self.free.is_empty() {
+            return None;
+        }
+
+        let greater = |item: &Option<T>| matches!(item, Some(item) if *item > value);
+        let position = match self.storage.iter().position(greater) {
+            Some(position) => position,
+            None => self.storage.iter().rposition(Option::is_some).map_or(0, |last| last + 1),
+        };
+
+        // Pick a vacant slot to consume and shift items between it and position towards it
+        let (index, vacant) = if position > 0 && self.storage[position - 1].is_none() {
+            (position - 1, position - 1)
+        } else if let Some(vacant) = self.storage[position..].iter().position(Option::is_none) {
+            let vacant = position + vacant;
+            self.storage[position..=vacant].rotate_right(1);
+            if !self.inserted_at.is_empty() {
+                self.inserted_at[position..=vacant].rotate_right(1);
+            }
+            (position, vacant)
+        } else {
+            let vacant = self.storage[..position].iter().rposition(Option::is_none)?;
+            self.storage[vacant..position].rotate_left(1);
+            if !self.inserted_at.is_empty() {
+                self.inserted_at[vacant..position].rotate_left(1);
+            }
+            (position - 1, vacant)
+        };
+
+        self.free.retain(|free| *free != vacant);
+        self.storage[index] = Some(value);
+        self.mark_inserted(index);
+        Some(index)
+    }
+
+    /// Collect items along with their indices, sorted by value and then by index
+    pub fn sorted_entries(&self) -> Vec<(usize, &T)> {
+        let mut entries: Vec<_> = self.entries().collect();
+        entries.sort_by(|(a_index, a), (b_index, b)| a.cmp(b).then(a_index.cmp(b_index)));
+        entries
+    }
+}
+
+impl<T: PartialEq> OchenSlab<T> {
+    /// Replace an item by its index with `new` only if it's equal to `expected`.
+    /// Returns the replaced item, or `new` back if the slot is vacant or holds something else.
+    /// Bumps revision on success
+    pub fn compare_and_replace(&mut self, index: usize, expected: &T, new: T) -> Result<T, T> {
+        match self.storage.get_mut(index) {
+            Some(Some(item)) if item == expected => {
+                self.revision = self.revision.wrapping_add(1);
+                Ok(std::mem::replace(item, new))
+            }
+            _ => Err(new),
+        }
+    }
+}
+
+impl<T: Default> OchenSlab<T> {
+    /// Insert a default value and return its index.
+    /// Returns None if there's no space left
+    pub fn insert_default(&mut self) -> Option<usize> {
+        self.insert(T::default())
+    }
+
+    /// Insert a default value and return its index along with mutable reference to it, for
+    /// setting it up in place.
+    /// Returns None if there's no space left
+    pub fn next_default_mut(&mut self) -> Option<(usize, &mut T)> {
+        let index = self.insert_default()?;
+        Some((index, self.storage[index].as_mut()?))
+    }
+
+    /// Get mutable reference to an item by its index, inserting a default value at exactly this
+    /// index first if the slot is vacant.
+    /// Returns None only if index is beyond capacity
+    pub fn entry_default(&mut self, index: usize) -> Option<&mut T> {
+        let slot = self.storage.get_mut(index)?;
+        if slot.is_none() {
+            *slot = Some(T::default());
+            self.free.retain(|free| *free != index);
+            self.mark_inserted(index);
+        } else {
+            self.revision = self.revision.wrapping_add(1);
+        }
+        self.storage[index].as_mut()
+    }
+}
+
+/// Inconsistency found by `OchenSlab::validate`, referring to the offending slot index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    /// Free list refers to an index beyond capacity
+    FreeOutOfRange(usize),
+
+    /// Free list refers to an occupied slot
+    FreeOccupied(usize),
+
+    /// Free list contains the same index more than once
+    FreeDuplicate(usize),
+
+    /// Vacant slot is missing from the free list
+    VacantNotFree(usize),
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Corruption::FreeOutOfRange(index) => write!(f, "free index {} is out of range", index),
+            Corruption::FreeOccupied(index) => write!(f, "free index {} is occupied", index),
+            Corruption::FreeDuplicate(index) => write!(f, "free index {} is listed twice", index),
+            Corruption::VacantNotFree(index) => write!(f, "vacant index {} is not free", index),
+        }
+    }
+}
+
+impl std::error::Error for Corruption {}
+
+/// Which item `OchenSlab::evict_one` removes
+///
+/// Insertion order is only known for slabs created by `OchenSlab::with_capacity_ordered`,
+/// others consider items inserted in ascending index order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictPolicy {
+    /// Least recently inserted item
+    Oldest,
+
+    /// Most recently inserted item
+    Newest,
+
+    /// Item with the lowest index
+    LowestIndex,
+
+    /// Item with the highest index
+    HighestIndex,
+}
+
+/// Error returned by `OchenSlab::compact_into` when target slab is too small
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactError {
+    /// Number of items to move
+    pub needed: usize,
+
+    /// Number of free slots in target slab
+    pub available: usize,
+}
+
+impl fmt::Display for CompactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} items don't fit into {} free slots", self.needed, self.available)
+    }
+}
+
+impl std::error::Error for CompactError {}
+
+/// Reason `OchenSlab::get2_mut_checked` or `OchenSlab::try_get_disjoint_mut` couldn't borrow
+/// items
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisjointError {
+    /// Both indices passed to `get2_mut_checked` are the same
+    SameIndex,
+
+    /// Index is given more than once to `try_get_disjoint_mut`
+    Duplicate(usize),
+
+    /// Index is beyond capacity
+    OutOfRange(usize),
+
+    /// Slot at index is vacant
+    Vacant(usize),
+}
+
+impl fmt::Display for DisjointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisjointError::SameIndex => write!(f, "indices are the same"),
+            DisjointError::Duplicate(index) => write!(f, "index {} is given more than once", index),
+            DisjointError::OutOfRange(index) => write!(f, "index {} is out of range", index),
+            DisjointError::Vacant(index) => write!(f, "slot {} is vacant", index),
+        }
+    }
+}
+
+impl std::error::Error for DisjointError {}
+
+/// Mutable references returned by `OchenSlab::get_pair`
+#[derive(Debug, PartialEq, Eq)]
+pub enum Pair<'a, T> {
+    /// Indices differ, references are in the same order as indices
+    Two(&'a mut T, &'a mut T),
+
+    /// Both indices are the same
+    One(&'a mut T),
+}
+
+/// Reason `OchenSlab::reindex` rejected a mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReindexError {
+    /// Item would be moved beyond capacity
+    OutOfRange {
+        /// Current index of the item
+        old: usize,
+        /// Index it was mapped to
+        new: usize,
+    },
+
+    /// Item would be moved into a slot occupied by another item
+    Occupied {
+        /// Current index of the item
+        old: usize,
+        /// Index it was mapped to
+        new: usize,
+    },
+
+    /// Item would be moved to an index another item was already mapped to
+    Collision {
+        /// Current index of the item
+        old: usize,
+        /// Index it was mapped to
+        new: usize,
+    },
+}
+
+impl fmt::Display for ReindexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReindexError::OutOfRange { old, new } => {
+                write!(f, "index {} is mapped out of range to {}", old, new)
+            }
+            ReindexError::Occupied { old, new } => {
+                write!(f, "index {} is mapped to occupied {}", old, new)
+            }
+            ReindexError::Collision { old, new } => {
+                write!(f, "index {} is mapped to already taken {}", old, new)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReindexError {}
+
+/// Reason `OchenSlab::rotate` rejected an offset: an item would land on another item's slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotateError {
+    /// Current index of the item
+    pub old: usize,
+
+    /// Occupied index it would be moved to
+    pub new: usize,
+}
+
+impl fmt::Display for RotateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "index {} would be moved to occupied {}", self.old, self.new)
+    }
+}
+
+impl std::error::Error for RotateError {}
+
+/// Reason `OchenSlab::insert_all_at` rejected items, referring to the offending slot index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertAtError {
+    /// Slot is already occupied
+    Occupied(usize),
+
+    /// Slot is beyond capacity
+    OutOfRange(usize),
+
+    /// Slot is given more than one item
+    Duplicate(usize),
+}
+
+impl fmt::Display for InsertAtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertAtError::Occupied(index) => write!(f, "slot {} is already occupied", index),
+            InsertAtError::OutOfRange(index) => write!(f, "slot {} is out of range", index),
+            InsertAtError::Duplicate(index) => write!(f, "slot {} is given more than once", index),
+        }
+    }
+}
+
+impl std::error::Error for InsertAtError {}
+
+/// View of occupied slots of an `OchenSlab` indexed by dense position in `0..len`
+///
+/// Position `n` refers to the `n`-th occupied slot in ascending index order.
+pub struct DenseView<'a, T> {
+    slab: &'a OchenSlab<T>,
+
+    // Slot index for each dense position
+    indices: Vec<usize>,
+}
+
+impl<'a, T> DenseView<'a, T> {
+    /// Return number of items in this view
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Return true if there are no items in this view
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Return slot index of an item at given dense position
+    pub fn slot_index(&self, position: usize) -> Option<usize> {
+        self.indices.get(position).copied()
+    }
+}
+
+impl<'a, T> Index<usize> for DenseView<'a, T> {
+    type Output = T;
+
+    fn index(&self, position: usize) -> &T {
+        let index = self.indices[position];
+        self.slab.storage[index].as_ref().expect("dense view refers to an empty slot")
+    }
+}
+
+/// Iterator over mutable references to items at distinct indices of an `OchenSlab`
+///
+/// Created by `OchenSlab::disjoint_iter_mut`.
+pub struct DisjointIterMut<'a, T, I> {
+    // Pointer to the first slot, valid for the whole lifetime as the slab is borrowed mutably
+    slots: NonNull<Option<T>>,
+
+    // Number of slots
+    capacity: usize,
+
+    // Slots that have been yielded already
+    seen: IndexSet,
+
+    // Revision of the slab, taken when bumped on the first yielded item
+    revision: Option<&'a mut u64>,
+
+    indices: I,
+
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T, I: Iterator<Item = usize>> Iterator for DisjointIterMut<'a, T, I> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        for index in self.indices.by_ref() {
+            if index >= self.capacity || !self.seen.insert(index) {
+                continue;
+            }
+            // Safety: index is in bounds, and no reference to this slot has been handed out
+            // before, as it is marked seen now
+            if let Some(item) = unsafe { (*self.slots.as_ptr().add(index)).as_mut() } {
+                if let Some(revision) = self.revision.take() {
+                    *revision = revision.wrapping_add(1);
+                }
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_insert_and_remove_an_element() {
+        let mut slab = OchenSlab::<usize>::with_capacity(8);
+        assert_eq!(slab.capacity(), 8);
+        let index = slab.insert(31337);
+        assert!(index.is_some());
+        assert_eq!(slab.len(), 1);
+        let index = index.unwrap();
+        let item = slab.get(index);
+        assert!(item.is_some());
+        let item = item.unwrap();
+        assert_eq!(*item, 31337);
+        slab.remove(index);
+        assert!(slab.get(index).is_none());
+        assert_eq!(slab.len(), 0);
+    }
+
+    #[test]
+    fn can_reach_capacity() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        assert_eq!(slab.capacity(), 4);
+        assert!(slab.insert(1).is_some());
+        assert!(slab.insert(2).is_some());
+        assert!(slab.insert(3).is_some());
+        assert!(slab.insert(4).is_some());
+        assert_eq!(slab.len(), 4);
+        assert!(slab.insert(5).is_none());
+    }
+
+    #[test]
+    fn can_reach_capacity_and_back() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        assert_eq!(slab.capacity(), 4);
+        assert!(slab.insert(1).is_some());
+        let index = slab.insert(2);
+        assert!(index.is_some());
+        assert!(slab.insert(3).is_some());
+        assert!(slab.insert(4).is_some());
+        assert!(slab.insert(5).is_none());
+        assert_eq!(slab.len(), 4);
+        let item = slab.remove(index.unwrap());
+        assert_eq!(slab.len(), 3);
+        assert!(item.is_some());
+        let item = item.unwrap();
+        assert_eq!(item, 2);
+        assert!(slab.insert(6).is_some());
+        assert_eq!(slab.len(), 4);
+        assert!(slab.insert(7).is_none());
+        assert_eq!(slab.len(), 4);
+    }
+
+    #[test]
+    fn can_mutate_element() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        assert_eq!(slab.capacity(), 4);
+        let index = slab.insert(1).expect("insert() failed");
+        let item = slab.get_mut(index).expect("get_mut() failed");
+        *item = 2;
+        let item = slab.get(index).expect("get() failed");
+        assert_eq!(*item, 2);
+    }
+
+    #[test]
+    fn can_borrow_two_distinct_items() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 0..3);
+        slab.remove(1);
+        let (a, b) = slab.get2_mut_checked(2, 0).expect("get2_mut_checked() failed");
+        std::mem::swap(a, b);
+        assert_eq!(slab.to_option_vec(), [Some(2), None, Some(0), None]);
+        assert_eq!(slab.get2_mut_checked(0, 0).err(), Some(DisjointError::SameIndex));
+        assert_eq!(slab.get2_mut_checked(0, 4).err(), Some(DisjointError::OutOfRange(4)));
+        assert_eq!(slab.get2_mut_checked(1, 2).err(), Some(DisjointError::Vacant(1)));
+    }
+
+    #[test]
+    fn prefetch_doesnt_change_anything() {
+        let slab = OchenSlab::from_iter_with_capacity(4, 0..2);
+        let revision = slab.revision();
+        slab.prefetch(0);
+        slab.prefetch(3);
+        slab.prefetch(4);
+        slab.prefetch(usize::MAX);
+        assert_eq!(slab.to_option_vec(), [Some(0), Some(1), None, None]);
+        assert_eq!(slab.revision(), revision);
+    }
+
+    #[test]
+    fn zeroed_slab_behaves_like_regular_one() {
+        let value = |v: u32| NonZeroU32::new(v + 1).expect("zero value");
+        let mut zeroed = OchenSlab::<NonZeroU32>::with_capacity_zeroed(100_000);
+        let mut regular = OchenSlab::<NonZeroU32>::with_capacity(100_000);
+        assert!(zeroed.is_empty());
+        assert_eq!(zeroed.capacity(), regular.capacity());
+        assert_eq!(zeroed.validate(), Ok(()));
+        assert!(zeroed.storage.iter().all(Option::is_none));
+        for v in 0..10 {
+            assert_eq!(zeroed.insert(value(v)), regular.insert(value(v)));
+        }
+        assert_eq!(zeroed.remove(3), regular.remove(3));
+        assert_eq!(zeroed.to_option_vec(), regular.to_option_vec());
+
+        let mut boxed = OchenSlab::<Box<usize>>::with_capacity_zeroed(3);
+        assert_eq!(boxed.insert(Box::new(1)), Some(0));
+        assert!(OchenSlab::<Box<u8>>::with_capacity_zeroed(0).is_empty());
+    }
+
+    #[test]
+    fn fresh_slab_inserts_in_ascending_order() {
+        let mut slab = OchenSlab::<usize>::with_capacity(6);
+        let indices: Vec<_> = (0..6).map(|value| slab.insert(value)).collect();
+        assert_eq!(indices, [Some(0), Some(1), Some(2), Some(3), Some(4), Some(5)]);
+    }
+
+    #[test]
+    fn extend_reporting_returns_overflow() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        assert_eq!(slab.extend_reporting(0..2).0, 2);
+        assert!(slab.extend_reporting(Vec::new()).1.is_none());
+        let (count, rest) = slab.extend_reporting(2..6);
+        assert_eq!(count, 2);
+        assert_eq!(rest.expect("no overflow").collect::<Vec<_>>(), [4, 5]);
+        assert_eq!(slab.to_option_vec(), [Some(0), Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn get_or_insert_by_finds_or_inserts() {
+        let mut slab = OchenSlab::<usize>::with_capacity(3);
+        slab.insert(10);
+        slab.insert(20);
+        let (index, value) = slab.get_or_insert_by(|value| *value == 20, || 0).expect("failed");
+        assert_eq!((index, *value), (1, 20));
+        let (index, value) = slab.get_or_insert_by(|value| *value == 30, || 30).expect("failed");
+        *value += 1;
+        assert_eq!(index, 2);
+        assert_eq!(slab.get(2), Some(&31));
+        assert!(slab.get_or_insert_by(|value| *value == 40, || 40).is_none());
+        assert_eq!(slab.len(), 3);
+    }
+
+    #[test]
+    fn insert_if_hands_back_rejected_items() {
+        let mut slab = OchenSlab::<usize>::with_capacity(2);
+        assert_eq!(slab.insert_if(2, |value| value % 2 == 0), Ok(0));
+        assert_eq!(slab.insert_if(3, |value| value % 2 == 0), Err(3));
+        assert_eq!(slab.len(), 1);
+        assert_eq!(slab.insert(5), Some(1));
+        assert_eq!(slab.insert_if(4, |_| panic!("full slab must not check items")), Err(4));
+    }
+
+    #[test]
+    fn insert_ring_evicts_slots_round_robin() {
+        let mut slab = OchenSlab::<usize>::with_capacity(3);
+        assert_eq!(slab.insert_ring(0), (0, None));
+        assert_eq!(slab.insert_ring(1), (1, None));
+        assert_eq!(slab.insert_ring(2), (2, None));
+        assert_eq!(slab.insert_ring(3), (0, Some(0)));
+        assert_eq!(slab.insert_ring(4), (1, Some(1)));
+        assert_eq!(slab.insert_ring(5), (2, Some(2)));
+        assert_eq!(slab.insert_ring(6), (0, Some(3)));
+        assert_eq!(slab.len(), 3);
+        slab.remove(2);
+        assert_eq!(slab.insert_ring(7), (2, None));
+        assert_eq!(slab.insert_ring(8), (1, Some(4)));
+    }
+
+    #[test]
+    fn validate_accepts_consistent_slab() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        assert_eq!(slab.validate(), Ok(()));
+        slab.insert(0).expect("insert() failed");
+        slab.insert(1).expect("insert() failed");
+        slab.remove(0);
+        assert_eq!(slab.validate(), Ok(()));
+        let (storage, free) = slab.into_raw_parts();
+        let slab = OchenSlab::from_raw_parts(storage, free);
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn validate_reports_corruption() {
+        let storage = vec![Some(0), None, None];
+        let cases = vec![
+            (vec![1, 2, 3], Corruption::FreeOutOfRange(3)),
+            (vec![0, 1, 2], Corruption::FreeOccupied(0)),
+            (vec![1, 2, 1], Corruption::FreeDuplicate(1)),
+            (vec![2], Corruption::VacantNotFree(1)),
+        ];
+        for (free, corruption) in cases {
+            let slab = OchenSlab::<usize>::from_raw_parts(storage.clone(), free);
+            assert_eq!(slab.validate(), Err(corruption));
+        }
+    }
+
+    #[test]
+    fn backing_layout_matches_storage() {
+        let slab = OchenSlab::<u32>::with_capacity(10);
+        let layout = slab.backing_layout();
+        assert_eq!(layout.size(), 80);
+        assert_eq!(layout.align(), 4);
+        assert_eq!(OchenSlab::<u64>::with_capacity(0).backing_layout().size(), 0);
+    }
+
+    #[test]
+    fn compact_into_moves_items_densely() {
+        let mut src = OchenSlab::from_iter_with_capacity(6, 0..5);
+        src.remove(0);
+        src.remove(2);
+        let mut dst = OchenSlab::<usize>::with_capacity(4);
+        assert_eq!(dst.insert(10), Some(0));
+        let moves = src.compact_into(&mut dst).expect("compact_into() failed");
+        assert_eq!(moves, [(1, 1), (3, 2), (4, 3)]);
+        assert!(src.is_empty());
+        assert_eq!(src.validate(), Ok(()));
+        assert_eq!(src.insert(5), Some(0));
+        assert_eq!(dst.to_option_vec(), [Some(10), Some(1), Some(3), Some(4)]);
+    }
+
+    #[test]
+    fn compact_into_rejects_small_target() {
+        let mut src = OchenSlab::from_iter_with_capacity(4, 0..3);
+        let mut dst = OchenSlab::from_iter_with_capacity(3, 10..11);
+        let error = src.compact_into(&mut dst).err();
+        assert_eq!(error, Some(CompactError { needed: 3, available: 2 }));
+        assert_eq!(src.to_option_vec(), [Some(0), Some(1), Some(2), None]);
+        assert_eq!(dst.to_option_vec(), [Some(10), None, None]);
+    }
+
+    #[test]
+    fn relocate_step_compacts_gradually() {
+        let mut slab = OchenSlab::from_iter_with_capacity(8, 0..7);
+        slab.retain(|index, _| index % 3 != 0);
+        assert_eq!(slab, &[None, Some(1), Some(2), None, Some(4), Some(5), None, None][..]);
+        let mut moves = Vec::new();
+        while let Some(step) = slab.relocate_step() {
+            moves.push(step);
+        }
+        assert_eq!(moves, [(1, 0), (2, 1), (4, 2), (5, 3)]);
+        assert!(slab.is_contiguous());
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.free_list_order().collect::<Vec<_>>(), [4, 5, 6, 7]);
+        assert_eq!(slab.insert(6), Some(4));
+    }
+
+    #[test]
+    fn shrink_to_fit_occupied_releases_vacant_slots() {
+        let mut slab = OchenSlab::from_iter_with_capacity(100, 0..100u64);
+        slab.retain(|index, _| index % 25 == 1);
+        let snapshot = slab.snapshot();
+        let freed = slab.shrink_to_fit_occupied();
+        assert_eq!(slab.capacity(), 4);
+        assert_eq!(slab.len(), 4);
+        assert_eq!(freed, 96 * (16 + 8));
+        for (new, old) in snapshot.into_iter().enumerate() {
+            assert_eq!(slab.get(new), Some(&(old as u64)));
+        }
+        assert!(slab.insert(0).is_none());
+        slab.remove(1);
+        assert_eq!(slab.insert(5), Some(1));
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.shrink_to_fit_occupied(), 0);
+    }
+
+    #[test]
+    fn shrink_to_fit_occupied_keeps_full_slab() {
+        let mut slab = OchenSlab::try_from_raw_parts(vec![Some(1u64), Some(2)], vec![])
+            .expect("parts are consistent");
+        assert_eq!(slab.shrink_to_fit_occupied(), 0);
+        assert_eq!(slab, &[Some(1), Some(2)][..]);
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn split_by_partitions_items() {
+        let mut slab = OchenSlab::from_iter_with_capacity(8, 0..6);
+        slab.remove(2);
+        let (even, odd) = slab.split_by(|_, value| value % 2 == 0);
+        assert_eq!(even.capacity(), 2);
+        assert_eq!(even.to_option_vec(), [Some(0), Some(4)]);
+        assert_eq!(odd.capacity(), 3);
+        assert_eq!(odd.to_option_vec(), [Some(1), Some(3), Some(5)]);
+    }
+
+    #[test]
+    fn sorted_entries_dont_depend_on_insertion_order() {
+        let values = [3, 1, 2, 1];
+        let forward = OchenSlab::from_iter_with_capacity(4, values.iter().copied());
+        let backward = OchenSlab::from_iter_with_capacity(4, values.iter().rev().copied());
+        let sorted = |slab: &OchenSlab<usize>| -> Vec<usize> {
+            slab.sorted_entries().into_iter().map(|(_, value)| *value).collect()
+        };
+        assert_eq!(sorted(&forward), [1, 1, 2, 3]);
+        assert_eq!(sorted(&forward), sorted(&backward));
+        assert_eq!(forward.sorted_entries(), [(1, &1), (3, &1), (2, &2), (0, &3)]);
+    }
+
+    #[test]
+    fn insert_sorted_keeps_items_sorted_by_index() {
+        let mut slab = OchenSlab::<usize>::with_capacity(6);
+        for value in [5, 1, 4, 1, 3].iter().copied() {
+            assert!(slab.insert_sorted(value).is_some());
+        }
+        let values: Vec<_> = slab.to_option_vec().into_iter().flatten().collect();
+        assert_eq!(values, [1, 1, 3, 4, 5]);
+        assert_eq!(slab.validate(), Ok(()));
+
+        slab.remove(1);
+        slab.remove(3);
+        assert_eq!(slab.insert_sorted(2), Some(1));
+        assert_eq!(slab.insert_sorted(6), Some(5));
+        assert_eq!(slab.insert_sorted(0), Some(0));
+        assert_eq!(slab.to_option_vec(), [Some(0), Some(1), Some(2), Some(3), Some(5), Some(6)]);
+        assert_eq!(slab.validate(), Ok(()));
+        assert!(slab.insert_sorted(7).is_none());
+    }
+
+    #[test]
+    fn entry_default_fills_vacant_slot() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        assert_eq!(slab.insert(7), Some(0));
+        *slab.entry_default(2).expect("entry_default() failed") += 1;
+        assert_eq!(slab.get(2), Some(&1));
+        assert_eq!(slab.entry_default(0), Some(&mut 7));
+        assert!(slab.entry_default(4).is_none());
+        assert_eq!(slab.len(), 2);
+        assert_eq!(slab.insert(8), Some(1));
+        assert_eq!(slab.insert(9), Some(3));
+        assert!(slab.insert(10).is_none());
+    }
+
+    #[test]
+    fn reindex_moves_items() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, 0..3);
+        let moves = slab.reindex(|old| old + 3).expect("reindex() failed");
+        assert_eq!(moves, [(0, 3), (1, 4), (2, 5)]);
+        assert_eq!(slab.to_option_vec(), [None, None, None, Some(0), Some(1), Some(2)]);
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.insert(3), Some(0));
+
+        let moves = slab.reindex(|old| match old {
+            4 => 1,
+            5 => 2,
+            old => old,
+        });
+        assert_eq!(moves, Ok(vec![(0, 0), (3, 3), (4, 1), (5, 2)]));
+        assert_eq!(slab.to_option_vec(), [Some(3), Some(1), Some(2), Some(0), None, None]);
+        assert_eq!(slab.insert(4), Some(4));
+    }
+
+    #[test]
+    fn reindex_rejects_invalid_mapping() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, 0..3);
+        assert_eq!(slab.reindex(|_| 3), Err(ReindexError::Collision { old: 1, new: 3 }));
+        assert_eq!(slab.reindex(|old| old + 3), Err(ReindexError::OutOfRange { old: 2, new: 5 }));
+        assert_eq!(slab.reindex(|old| 2 - old), Err(ReindexError::Occupied { old: 0, new: 2 }));
+        assert_eq!(slab.to_option_vec(), [Some(0), Some(1), Some(2), None, None]);
+    }
+
+    #[test]
+    fn can_change_slots_directly() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, 0..4);
+        let slots = slab.as_mut_slots();
+        slots[1] = None;
+        slots[2] = None;
+        slots[4] = Some(4);
+        slab.rebuild_free_list();
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.len(), 3);
+        assert_eq!(slab.insert(5), Some(1));
+        assert_eq!(slab.insert(6), Some(2));
+        assert!(slab.insert(7).is_none());
+    }
+
+    #[test]
+    fn can_fill_from_iterator_with_capacity() {
+        let slab = OchenSlab::from_iter_with_capacity(5, 1..4);
+        assert_eq!(slab.len(), 3);
+        assert_eq!(slab.capacity(), 5);
+        assert_eq!(slab.get(2), Some(&3));
+
+        let mut iter = 0..10;
+        let slab = OchenSlab::from_iter_with_capacity(4, iter.by_ref());
+        assert_eq!(slab.len(), 4);
+        assert_eq!(iter.next(), Some(4));
+    }
+
+    #[test]
+    fn contiguous_slab_has_no_holes() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        assert!(slab.is_contiguous());
+        slab.insert(0);
+        slab.insert(1);
+        slab.insert(2);
+        assert!(slab.is_contiguous());
+        slab.remove(1);
+        assert!(!slab.is_contiguous());
+        slab.remove(2);
+        assert!(slab.is_contiguous());
+        slab.remove(0);
+        slab.entry_default(3);
+        assert!(!slab.is_contiguous());
+    }
+
+    #[test]
+    fn occupied_mask_reflects_occupancy() {
+        let mut slab = OchenSlab::from_iter_with_capacity(8, 0..6);
+        slab.remove(1);
+        slab.remove(4);
+        assert_eq!(slab.occupied_mask_u64(), Some(0b0010_1101));
+        let full = OchenSlab::new_filled(64, 0);
+        assert_eq!(full.occupied_mask_u64(), Some(u64::MAX));
+        assert_eq!(OchenSlab::<usize>::with_capacity(100).occupied_mask_u64(), None);
+    }
+
+    #[test]
+    fn utilization_reports_fraction_of_occupied_slots() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        assert_eq!(slab.utilization(), 0.0);
+        slab.insert(0);
+        slab.insert(1);
+        assert_eq!(slab.utilization(), 0.5);
+        slab.insert(2);
+        slab.insert(3);
+        assert_eq!(slab.utilization(), 1.0);
+        assert_eq!(OchenSlab::<usize>::with_capacity(0).utilization(), 0.0);
+    }
+
+    #[test]
+    fn revision_is_bumped_by_successful_mutations_only() {
+        let mut slab = OchenSlab::<usize>::with_capacity(2);
+        assert_eq!(slab.insert(1), Some(0));
+        assert_eq!(slab.insert(2), Some(1));
+        type Op = fn(&mut OchenSlab<usize>);
+        let cases: Vec<(&str, Op, bool)> = vec![
+            ("get", |slab| { slab.get(0); }, false),
+            ("insert into full", |slab| { slab.insert(3); }, false),
+            ("get_mut", |slab| { slab.get_mut(0); }, true),
+            ("get_mut vacant", |slab| { slab.get_mut(5); }, false),
+            ("remove", |slab| { slab.remove(1); }, true),
+            ("remove vacant", |slab| { slab.remove(1); }, false),
+            ("insert", |slab| { slab.insert(4); }, true),
+            ("shift_remove", |slab| { slab.shift_remove(0); }, true),
+            ("shift_remove vacant", |slab| { slab.shift_remove(1); }, false),
+            ("split_ranges", |slab| slab.split_ranges(&[1], |_| ()), true),
+            ("retain_compacting noop", |slab| { slab.retain_compacting(|_, _| true); }, false),
+            ("disjoint_iter_mut vacant", |slab| { slab.disjoint_iter_mut([1, 5]).count(); }, false),
+            ("disjoint_iter_mut", |slab| { slab.disjoint_iter_mut([0]).count(); }, true),
+            ("split_mut", |slab| { slab.split_mut(); }, true),
+            ("retain_compacting", |slab| { slab.retain_compacting(|_, _| false); }, true),
+            ("split_mut empty", |slab| { slab.split_mut(); }, false),
+        ];
+        for (name, op, bumps) in cases {
+            let before = slab.revision();
+            op(&mut slab);
+            assert_eq!(slab.revision() > before, bumps, "{}", name);
+            assert!(slab.revision() >= before, "{}", name);
+        }
+    }
+
+    #[test]
+    fn retain_reports_kept_and_removed_counts() {
+        let mut slab = OchenSlab::from_iter_with_capacity(8, 0..6);
+        slab.remove(0);
+        assert_eq!(slab.retain_reporting(|_, value| value % 2 == 0), (2, 3));
+        assert_eq!(slab.len(), 2);
+        assert_eq!(slab.to_option_vec()[..6], [None, None, Some(2), None, Some(4), None]);
+        slab.retain(|index, _| index > 2);
+        assert_eq!(slab.len(), 1);
+        assert_eq!(slab.retain_reporting(|_, _| true), (1, 0));
+    }
+
+    #[test]
+    fn drain_sorted_by_empties_slab_in_given_order() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, vec![3, 1, 4, 1]);
+        let drained: Vec<_> = slab.drain_sorted_by(|a, b| b.cmp(a)).collect();
+        assert_eq!(drained, [(2, 4), (0, 3), (1, 1), (3, 1)]);
+        assert!(slab.is_empty());
+        assert_eq!(slab.insert(5), Some(0));
+
+        let mut drain = slab.drain_sorted_by(Ord::cmp);
+        assert_eq!(drain.next(), Some((0, 5)));
+        drop(drain);
+        assert!(slab.is_empty());
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn retain_recent_keeps_most_recently_inserted_items() {
+        let mut slab = OchenSlab::<usize>::with_capacity_ordered(6);
+        for value in 0..6 {
+            slab.insert(value);
+        }
+        slab.remove(1);
+        slab.remove(4);
+        assert_eq!(slab.insert(6), Some(4));
+        assert_eq!(slab.retain_recent(5), []);
+        assert_eq!(slab.retain_recent(3), [(0, 0), (2, 2)]);
+        assert_eq!(slab.to_option_vec(), [None, None, None, Some(3), Some(6), Some(5)]);
+        assert_eq!(slab.insert_ring(7), (2, None));
+        assert_eq!(slab.retain_recent(1), [(3, 3), (5, 5), (4, 6)]);
+        assert_eq!(slab.to_option_vec()[2], Some(7));
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn evict_one_follows_policy() {
+        let mut slab = OchenSlab::<usize>::with_capacity_ordered(6);
+        for value in 0..5 {
+            slab.insert(value);
+        }
+        slab.remove(1);
+        assert_eq!(slab.insert(5), Some(1));
+        assert_eq!(slab.evict_one(EvictPolicy::Oldest), Some((0, 0)));
+        assert_eq!(slab.evict_one(EvictPolicy::Newest), Some((1, 5)));
+        assert_eq!(slab.evict_one(EvictPolicy::LowestIndex), Some((2, 2)));
+        assert_eq!(slab.evict_one(EvictPolicy::HighestIndex), Some((4, 4)));
+        assert_eq!(slab.evict_one(EvictPolicy::Oldest), Some((3, 3)));
+        assert_eq!(slab.evict_one(EvictPolicy::Newest), None);
+    }
+
+    #[test]
+    fn try_retain_completes_sweep() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, 0..5);
+        let result: ControlFlow<()> = slab.try_retain(|_, value| {
+            *value *= 10;
+            ControlFlow::Continue(*value != 20)
+        });
+        assert_eq!(result, ControlFlow::Continue(()));
+        assert_eq!(slab, &[Some(0), Some(10), None, Some(30), Some(40)][..]);
+    }
+
+    #[test]
+    fn try_retain_stops_on_break() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, 0..5);
+        let result = slab.try_retain(|index, value| {
+            if index == 3 {
+                return ControlFlow::Break("deadline");
+            }
+            *value *= 10;
+            ControlFlow::Continue(index != 1)
+        });
+        assert_eq!(result, ControlFlow::Break("deadline"));
+        assert_eq!(slab, &[Some(0), None, Some(20), Some(3), Some(4)][..]);
+    }
+
+    #[test]
+    fn retain_with_budget_resumes_sweep() {
+        let mut slab = OchenSlab::from_iter_with_capacity(10, 0..10);
+        let small = |_: usize, value: &usize| *value < 5;
+        assert_eq!(slab.retain_with_budget(2, small), (2, false));
+        assert_eq!(slab.len(), 8);
+        assert_eq!(slab.retain_with_budget(2, small), (2, false));
+        assert_eq!(slab.retain_with_budget(2, small), (1, true));
+        assert_eq!(slab.to_option_vec().into_iter().flatten().collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+        assert_eq!(slab.retain_with_budget(2, small), (0, true));
+        assert_eq!(slab.retain_with_budget(0, |_, _| false), (0, false));
+    }
+
+    #[test]
+    fn swap_remove_reports_moved_item() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, 0..5);
+        slab.remove(3);
+        assert_eq!(slab.swap_remove_return_moved(1), Some((1, Some(4))));
+        assert_eq!(slab, &[Some(0), Some(4), Some(2), None, None, None][..]);
+        assert_eq!(slab.swap_remove_return_moved(2), Some((2, None)));
+        assert_eq!(slab.swap_remove_return_moved(2), None);
+        assert_eq!(slab.len(), 2);
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn shift_remove_keeps_elements_packed_and_ordered() {
+        let mut slab = OchenSlab::<usize>::with_capacity(8);
+        for value in 0..5 {
+            assert_eq!(slab.insert(value * 10), Some(value));
+        }
+        assert_eq!(slab.shift_remove(1), Some(10));
+        assert_eq!(slab.len(), 4);
+        let values: Vec<_> = (0..4).map(|index| *slab.get(index).expect("get() failed")).collect();
+        assert_eq!(values, [0, 20, 30, 40]);
+        assert!(slab.get(4).is_none());
+        assert!(slab.shift_remove(4).is_none());
+        assert_eq!(slab.insert(50), Some(4));
+    }
+
+    #[test]
+    fn dense_view_skips_holes() {
+        let mut slab = OchenSlab::<usize>::with_capacity(8);
+        for value in 0..6 {
+            slab.insert(value).expect("insert() failed");
+        }
+        slab.remove(0);
+        slab.remove(2);
+        slab.remove(5);
+        let view = slab.dense_view();
+        assert_eq!(view.len(), 3);
+        assert_eq!(view[0], 1);
+        assert_eq!(view[1], 3);
+        assert_eq!(view[view.len() - 1], 4);
+        assert_eq!(view.slot_index(1), Some(3));
+        assert_eq!(view.slot_index(3), None);
+    }
+
+    #[test]
+    fn can_find_nth_occupied_slot() {
+        let mut slab = OchenSlab::<usize>::with_capacity(8);
+        assert_eq!(slab.nth_occupied(0), None);
+        for value in 0..5 {
+            slab.insert(value).expect("insert() failed");
+        }
+        slab.remove(1);
+        slab.remove(3);
+        assert_eq!(slab.nth_occupied(0), Some(0));
+        assert_eq!(slab.nth_occupied(1), Some(2));
+        assert_eq!(slab.nth_occupied(2), Some(4));
+        assert_eq!(slab.nth_occupied(slab.len()), None);
+    }
+
+    #[test]
+    fn can_iterate_nested_slabs() {
+        let mut slab = OchenSlab::<OchenSlab<usize>>::with_capacity(4);
+        for outer in 0..3 {
+            let values = (0..3).map(|inner| outer * 10 + inner);
+            slab.insert(OchenSlab::from_iter_with_capacity(3, values));
+        }
+        slab.remove(1);
+        slab.get_mut(2).expect("get_mut() failed").remove(1);
+        let items: Vec<_> = slab.iter_nested().collect();
+        assert_eq!(items, [((0, 0), &0), ((0, 1), &1), ((0, 2), &2), ((2, 0), &20), ((2, 2), &22)]);
+    }
+
+    #[test]
+    fn snapshot_survives_mutation() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, 0..4);
+        let mut visited = Vec::new();
+        for index in slab.snapshot() {
+            let value = match slab.get(index) {
+                Some(value) => *value,
+                None => continue,
+            };
+            visited.push(value);
+            if value == 1 {
+                slab.insert(10);
+                slab.remove(2);
+            }
+        }
+        assert_eq!(visited, [0, 1, 3]);
+        assert_eq!(slab.snapshot(), [0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn iter_chunks_yields_batches_of_items() {
+        let mut slab = OchenSlab::from_iter_with_capacity(8, 0..8);
+        slab.remove(2);
+        let sizes: Vec<_> = slab.iter_chunks(3).map(|chunk| chunk.len()).collect();
+        assert_eq!(sizes, [3, 3, 1]);
+        let first = slab.iter_chunks(3).next().expect("iter_chunks() is empty");
+        assert_eq!(first, [(0, &0), (1, &1), (3, &3)]);
+        assert_eq!(slab.iter_chunks(7).count(), 1);
+        assert_eq!(OchenSlab::<usize>::with_capacity(4).iter_chunks(2).count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn iter_chunks_rejects_zero_chunk_size() {
+        let slab = OchenSlab::<usize>::with_capacity(4);
+        slab.iter_chunks(0).next();
+    }
+
+    #[test]
+    fn histogram_counts_items_per_bucket() {
+        let mut slab = OchenSlab::from_iter_with_capacity(8, 0..7);
+        slab.remove(2);
+        let histogram = slab.histogram(|value| value % 2 == 0);
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram[&true], 3);
+        assert_eq!(histogram[&false], 3);
+        assert!(OchenSlab::<usize>::with_capacity(2).histogram(|value| *value).is_empty());
+    }
+
+    #[test]
+    fn can_look_up_items_generically() {
+        fn sum(items: &impl IndexLookup<usize>, indices: &[usize]) -> usize {
+            indices.iter().filter_map(|index| items.lookup(*index)).sum()
+        }
+
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 1..4);
+        slab.remove(1);
+        assert_eq!(sum(&slab, &[0, 1, 2, 3]), 4);
+    }
+
+    #[test]
+    fn can_compare_with_slot_slice() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 1..4);
+        slab.remove(1);
+        assert_eq!(slab, &[Some(1), None, Some(3), None][..]);
+        assert!(slab == [Some(1), None, Some(3), None][..]);
+        assert!(slab != [Some(1), None, Some(3)][..]);
+        assert_eq!(format!("{:?}", slab), "{0: 1, 2: 3}");
+    }
+
+    #[test]
+    fn new_filled_slab_is_full() {
+        let mut slab = OchenSlab::new_filled(3, 7usize);
+        assert_eq!(slab, &[Some(7), Some(7), Some(7)][..]);
+        assert_eq!(slab.len(), 3);
+        assert!(slab.insert(8).is_none());
+        assert_eq!(slab.remove(1), Some(7));
+        assert_eq!(slab.insert(8), Some(1));
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn clone_filtered_keeps_indices() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, 0..5);
+        slab.remove(2);
+        let even = slab.clone_filtered(|value| value % 2 == 0);
+        assert_eq!(even, &[Some(0), None, None, None, Some(4), None][..]);
+        assert_eq!(even.validate(), Ok(()));
+        assert_eq!(slab.len(), 4);
+    }
+
+    #[test]
+    fn clone_filtered_keeps_copied_items_older() {
+        let mut slab = OchenSlab::with_capacity_ordered(4);
+        slab.insert_all(vec![0, 1, 2]).expect("insert_all() failed");
+        let mut clone = slab.clone_filtered(|_| true);
+        let revision = clone.revision();
+        assert_eq!(clone.insert(99), Some(3));
+        assert_eq!(clone.iter_since(revision).collect::<Vec<_>>(), [(3, &99)]);
+        assert_eq!(clone.evict_one(EvictPolicy::Newest), Some((3, 99)));
+    }
+
+    #[test]
+    fn to_option_vec_preserves_holes() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        for value in 0..3 {
+            slab.insert(value).expect("insert() failed");
+        }
+        slab.remove(1);
+        assert_eq!(slab.to_option_vec(), [Some(0), None, Some(2), None]);
+    }
+
+    #[test]
+    fn scope_removes_inserted_items() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        let kept = slab.insert(1).expect("insert() failed");
+        let removed = slab.insert(2).expect("insert() failed");
+        let index = slab.scope(|scope| {
+            let index = scope.insert(3).expect("insert() failed");
+            assert_eq!(scope.len(), 3);
+            assert_eq!(scope.remove(removed), Some(2));
+            index
+        });
+        assert!(slab.get(index).is_none());
+        assert!(slab.get(removed).is_none());
+        assert_eq!(slab.get(kept), Some(&1));
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn scope_removes_inserted_items_on_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        slab.insert(1).expect("insert() failed");
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            slab.scope(|scope| {
+                scope.insert(2).expect("insert() failed");
+                panic!("oops");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn nested_scopes_clean_up_independently() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        slab.scope(|outer| {
+            let a = outer.insert(1).expect("insert() failed");
+            let b = outer.scope(|inner| {
+                assert!(inner.get(a).is_some());
+                inner.insert(2).expect("insert() failed")
+            });
+            assert!(outer.get(b).is_none());
+            assert_eq!(outer.len(), 1);
+        });
+        assert_eq!(slab.len(), 0);
+    }
+
+    #[test]
+    fn scope_doesnt_remove_reused_slot_twice() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        slab.scope(|scope| {
+            let a = scope.insert(1).expect("insert() failed");
+            assert_eq!(scope.remove(a), Some(1));
+            let b = scope.insert(2).expect("insert() failed");
+            assert_eq!(a, b);
+        });
+        assert_eq!(slab.len(), 0);
+        assert_eq!(slab.free.len(), 4);
+    }
+
+    #[test]
+    fn intersect_indices_yields_shared_slots() {
+        let mut positions = OchenSlab::from_iter_with_capacity(6, 0..6);
+        positions.remove(1);
+        positions.remove(4);
+        let mut velocities = OchenSlab::from_iter_with_capacity(4, "abcd".chars());
+        velocities.remove(2);
+        assert_eq!(positions.intersect_indices(&velocities).collect::<Vec<_>>(), [0, 3]);
+        assert_eq!(velocities.intersect_indices(&positions).collect::<Vec<_>>(), [0, 3]);
+    }
+
+    #[test]
+    fn can_insert_default() {
+        let mut slab = OchenSlab::<(u32, String)>::with_capacity(1);
+        let index = slab.insert_default().expect("insert_default() failed");
+        assert_eq!(slab.get(index), Some(&(0, String::new())));
+        assert!(slab.insert_default().is_none());
+    }
+
+    #[test]
+    fn pop_last_takes_highest_index() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, 10..14);
+        slab.remove(1);
+        assert_eq!(slab.pop_last(), Some((3, 13)));
+        assert_eq!(slab.pop_last(), Some((2, 12)));
+        assert_eq!(slab.len(), 1);
+        assert!(slab.get(2).is_none());
+        assert_eq!(slab.pop_last(), Some((0, 10)));
+        assert_eq!(slab.pop_last(), None);
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn iter_raw_points_at_items() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, vec![1u64, 2, 3]);
+        slab.remove(1);
+        let raw: Vec<_> = slab.iter_raw().collect();
+        assert_eq!(raw.len(), 2);
+        for (index, ptr) in raw {
+            // Safety: slab is not mutated while pointers are in use
+            let value = unsafe { ptr.as_ref() };
+            assert_eq!(Some(value), slab.get(index));
+            assert!(std::ptr::eq(value, slab.get(index).unwrap()));
+        }
+    }
+
+    #[test]
+    fn retain_sorted_indices_removes_others() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, 0..5);
+        slab.remove(3);
+        slab.retain_sorted_indices(&[1, 3, 4, 7]);
+        assert_eq!(slab, &[None, Some(1), None, None, Some(4), None][..]);
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.free.len(), 4);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "indices are not sorted")]
+    fn retain_sorted_indices_rejects_unsorted() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 0..4);
+        slab.retain_sorted_indices(&[2, 1]);
+    }
+
+    #[test]
+    fn clone_map_keeps_layout() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, vec![1, 2, 3]);
+        slab.remove(1);
+        let strings = slab.clone_map(i32::to_string);
+        assert_eq!(strings, &[Some("1".to_string()), None, Some("3".to_string()), None][..]);
+        assert_eq!(strings.free, slab.free);
+        assert_eq!(slab.get(0), Some(&1));
+    }
+
+    #[test]
+    fn clone_map_keeps_copied_items_older() {
+        let mut slab = OchenSlab::with_capacity_ordered(4);
+        slab.insert_all(vec![0, 1, 2]).expect("insert_all() failed");
+        let mut clone = slab.clone_map(|value| value * 10);
+        let revision = clone.revision();
+        assert_eq!(clone.insert(99), Some(3));
+        assert_eq!(clone.iter_since(revision).collect::<Vec<_>>(), [(3, &99)]);
+        assert_eq!(clone.evict_one(EvictPolicy::Newest), Some((3, 99)));
+        assert_eq!(clone.evict_one(EvictPolicy::Oldest), Some((0, 0)));
+    }
+
+    #[test]
+    fn can_map_in_place() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 1..4);
+        slab.remove(1);
+        let revision = slab.revision();
+        slab.map_in_place(|value| *value *= 2);
+        assert_eq!(slab, &[Some(2), None, Some(6), None][..]);
+        assert_ne!(slab.revision(), revision);
+    }
+
+    #[test]
+    fn get_many_mut_lenient_skips_duplicates() {
+        let mut slab = OchenSlab::from_iter_with_capacity(3, 0..3);
+        let [a, b, c] = slab.get_many_mut_lenient([1, 1, 2]);
+        assert_eq!(b, None);
+        *a.expect("first occurrence must be borrowed") += 10;
+        *c.expect("distinct index must be borrowed") += 20;
+        assert_eq!(slab, &[Some(0), Some(11), Some(22)][..]);
+    }
+
+    #[test]
+    fn get_many_mut_lenient_skips_invalid_indices() {
+        let mut slab = OchenSlab::from_iter_with_capacity(3, 0..3);
+        slab.remove(0);
+        let revision = slab.revision();
+        let [vacant, valid, out_of_range] = slab.get_many_mut_lenient([0, 2, 5]);
+        assert!(vacant.is_none());
+        assert!(out_of_range.is_none());
+        assert_eq!(valid, Some(&mut 2));
+        assert_ne!(slab.revision(), revision);
+
+        let revision = slab.revision();
+        assert_eq!(slab.get_many_mut_lenient([0, 7]), [None, None]);
+        assert_eq!(slab.revision(), revision);
+    }
+
+    #[test]
+    fn pop_any_returns_every_item_once() {
+        let mut slab = OchenSlab::from_iter_with_capacity(8, 0..6);
+        slab.remove(2);
+        let mut popped = Vec::new();
+        while let Some((index, value)) = slab.pop_any() {
+            assert_eq!(index, value);
+            popped.push(value);
+        }
+        popped.sort_unstable();
+        assert_eq!(popped, [0, 1, 3, 4, 5]);
+        assert!(slab.is_empty());
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn pop_any_finds_items_inserted_during_drain() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, 0..6);
+        assert_eq!(slab.pop_any(), Some((5, 5)));
+        assert_eq!(slab.pop_any(), Some((4, 4)));
+        assert_eq!(slab.pop_any(), Some((3, 3)));
+        assert_eq!(slab.insert(10), Some(3));
+        assert_eq!(slab.insert(11), Some(4));
+        let mut popped = Vec::new();
+        while let Some((_, value)) = slab.pop_any() {
+            popped.push(value);
+        }
+        assert_eq!(popped, [2, 1, 0, 11, 10]);
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn can_swap_contents() {
+        let mut front = OchenSlab::from_iter_with_capacity(3, vec!['a', 'b']);
+        let mut back = OchenSlab::with_capacity_ordered(3);
+        back.insert('x');
+        back.insert('y');
+        back.insert('z');
+        back.remove(0);
+        let revision = front.revision().max(back.revision());
+        front.swap_contents(&mut back);
+        assert_eq!(front, &[None, Some('y'), Some('z')][..]);
+        assert_eq!(back, &[Some('a'), Some('b'), None][..]);
+        assert!(front.revision() > revision && back.revision() > revision);
+        assert_eq!(front.insert('w'), Some(0));
+        assert_eq!(front.evict_one(EvictPolicy::Newest), Some((0, 'w')));
+    }
+
+    #[test]
+    #[should_panic(expected = "slab capacities differ")]
+    fn swap_contents_rejects_different_capacities() {
+        let mut a = OchenSlab::<u8>::with_capacity(2);
+        let mut b = OchenSlab::<u8>::with_capacity(3);
+        a.swap_contents(&mut b);
+    }
+
+    #[test]
+    fn get_pair_borrows_distinct_items() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 0..3);
+        match slab.get_pair(2, 0) {
+            Some(Pair::Two(a, b)) => std::mem::swap(a, b),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(slab, &[Some(2), Some(1), Some(0), None][..]);
+        assert_eq!(slab.get_pair(0, 3), None);
+        assert_eq!(slab.get_pair(0, 9), None);
+    }
+
+    #[test]
+    fn get_pair_tolerates_same_index() {
+        let mut slab = OchenSlab::from_iter_with_capacity(2, 0..1);
+        assert_eq!(slab.get_pair(0, 0), Some(Pair::One(&mut 0)));
+        assert_eq!(slab.get_pair(1, 1), None);
+    }
+
+    #[test]
+    fn untracked_insertion_order_follows_indices() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        slab.insert(0);
+        slab.insert(1);
+        slab.remove(0);
+        assert_eq!(slab.insert(2), Some(0));
+        assert!(slab.inserted_at.is_empty());
+        assert_eq!(slab.evict_one(EvictPolicy::Newest), Some((1, 1)));
+        assert_eq!(slab.evict_one(EvictPolicy::Oldest), Some((0, 2)));
+    }
+
+    #[test]
+    fn evict_one_skips_removed_items() {
+        let mut slab = OchenSlab::<char>::with_capacity_ordered(6);
+        for value in "abcdef".chars() {
+            slab.insert(value);
+        }
+        slab.remove(2);
+        slab.retain(|index, _| index != 0);
+        assert_eq!(slab.swap_remove_return_moved(1), Some(('b', Some(5))));
+        assert_eq!(slab.evict_one(EvictPolicy::Oldest), Some((3, 'd')));
+        assert_eq!(slab.shift_remove(1), Some('f'));
+        assert_eq!(slab.evict_one(EvictPolicy::Oldest), Some((3, 'e')));
+        assert_eq!(slab.evict_one(EvictPolicy::Oldest), None);
+
+        slab.insert('g');
+        slab.insert('h');
+        slab.remove(slab.snapshot()[1]);
+        slab.insert('i');
+        assert_eq!(slab.evict_one(EvictPolicy::Newest).map(|(_, value)| value), Some('i'));
+        assert_eq!(slab.evict_one(EvictPolicy::Oldest).map(|(_, value)| value), Some('g'));
+    }
+
+    #[test]
+    fn with_capacity_starts_vacant_with_descending_free_list() {
+        let slab = OchenSlab::<String>::with_capacity(5);
+        assert!(slab.storage.iter().all(Option::is_none));
+        assert_eq!(slab.free, [4, 3, 2, 1, 0]);
+        assert_eq!(slab.storage.capacity(), 5);
+        assert_eq!(slab.free.capacity(), 5);
+        assert!(OchenSlab::<String>::with_capacity(0).free.is_empty());
+    }
+
+    #[test]
+    fn iter_with_aligns_side_array() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, 0..5);
+        slab.remove(1);
+        let names = ["zero", "one", "two", "three"];
+        let zipped: Vec<_> = slab.iter_with(&names).collect();
+        assert_eq!(zipped, [(0, &0, &"zero"), (2, &2, &"two"), (3, &3, &"three")]);
+    }
+
+    #[test]
+    fn can_set_up_next_default_in_place() {
+        let mut slab = OchenSlab::<Vec<u8>>::with_capacity(1);
+        let (index, value) = slab.next_default_mut().expect("next_default_mut() failed");
+        assert!(value.is_empty());
+        value.push(7);
+        assert_eq!(slab.get(index), Some(&vec![7]));
+        assert!(slab.next_default_mut().is_none());
+    }
+
+    #[test]
+    fn rebuild_free_list_repairs_corruption() {
+        let storage = vec![Some(1), None, Some(3), None];
+        let mut slab = OchenSlab::from_raw_parts(storage, vec![0, 1, 1, 7]);
+        assert!(slab.validate().is_err());
+        slab.rebuild_free_list();
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.len(), 2);
+        assert_eq!(slab.insert(2), Some(1));
+        assert_eq!(slab.insert(4), Some(3));
+        assert_eq!(slab.insert(5), None);
+        assert_eq!(slab, &[Some(1), Some(2), Some(3), Some(4)][..]);
+    }
+
+    #[test]
+    fn remove_map_computes_before_removal() {
+        let mut slab = OchenSlab::from_iter_with_capacity(3, vec![("a", Some(1)), ("b", None)]);
+        assert_eq!(slab.remove_map(0, |(_, next)| *next), Some((("a", Some(1)), Some(1))));
+        assert!(slab.get(0).is_none());
+        assert_eq!(slab.remove_map(0, |(_, next)| *next), None);
+        assert_eq!(slab.remove_map(2, |(_, next)| *next), None);
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn drain_while_stops_at_first_rejected() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, vec![1, 2, 3, 10, 4]);
+        slab.remove(1);
+        assert_eq!(slab.drain_while(|_, value| *value < 5), [(0, 1), (2, 3)]);
+        assert_eq!(slab, &[None, None, None, Some(10), Some(4), None][..]);
+        assert_eq!(slab.drain_while(|_, _| false), []);
+        assert_eq!(slab.drain_while(|_, _| true).len(), 2);
+        assert!(slab.is_empty());
+    }
+
+    #[test]
+    fn can_insert_all_at_given_indices() {
+        let mut slab = OchenSlab::with_capacity_ordered(5);
+        slab.insert('a');
+        assert_eq!(slab.insert_all_at(vec![(3, 'd'), (1, 'b')]), Ok(()));
+        assert_eq!(slab, &[Some('a'), Some('b'), None, Some('d'), None][..]);
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.evict_one(EvictPolicy::Newest), Some((1, 'b')));
+        assert_eq!(slab.insert('c'), Some(1));
+    }
+
+    #[test]
+    fn insert_all_at_reports_conflicts() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, vec!['a']);
+        assert_eq!(slab.insert_all_at(vec![(2, 'c'), (0, 'x')]), Err(InsertAtError::Occupied(0)));
+        assert_eq!(slab.insert_all_at(vec![(2, 'c'), (4, 'e')]), Err(InsertAtError::OutOfRange(4)));
+        assert_eq!(slab.insert_all_at(vec![(2, 'c'), (2, 'x')]), Err(InsertAtError::Duplicate(2)));
+        assert_eq!(slab, &[Some('a'), None, None, None][..]);
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn with_byte_budget_fits_budget() {
+        let slot_bytes = std::mem::size_of::<Option<u32>>() + std::mem::size_of::<usize>();
+        let budget = 10 * slot_bytes + slot_bytes / 2;
+        let slab = OchenSlab::<u32>::with_byte_budget(budget);
+        assert_eq!(slab.capacity(), 10);
+        assert!(slab.allocated_bytes() <= budget);
+        assert_eq!(OchenSlab::<u32>::with_byte_budget(0).capacity(), 0);
+    }
+
+    #[test]
+    fn take_if_checks_predicate() {
+        let mut slab = OchenSlab::from_iter_with_capacity(3, vec!["idle", "busy"]);
+        assert_eq!(slab.take_if(1, |state| *state == "idle"), None);
+        assert_eq!(slab.get(1), Some(&"busy"));
+        assert_eq!(slab.take_if(0, |state| *state == "idle"), Some("idle"));
+        assert!(slab.get(0).is_none());
+        assert_eq!(slab.take_if(0, |_| true), None);
+        assert_eq!(slab.take_if(2, |_| true), None);
+        assert_eq!(slab.take_if(5, |_| true), None);
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn free_list_order_matches_inserts() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, 0..4);
+        slab.remove(1);
+        slab.remove(3);
+        slab.remove(0);
+        let order: Vec<_> = slab.free_list_order().collect();
+        assert_eq!(order, [0, 3, 1, 4, 5]);
+        let inserted: Vec<_> = (0..5).map(|value| slab.insert(value).unwrap()).collect();
+        assert_eq!(inserted, order);
+        assert_eq!(slab.free_list_order().next(), None);
+    }
+
+    #[test]
+    fn iter_since_yields_later_items() {
+        let mut slab = OchenSlab::with_capacity_ordered(6);
+        slab.insert('a');
+        slab.insert('b');
+        let phase = slab.revision();
+        slab.remove(0);
+        slab.insert('c');
+        slab.insert('d');
+        *slab.get_mut(1).unwrap() = 'B';
+        assert_eq!(slab.iter_since(phase).collect::<Vec<_>>(), [(0, &'c'), (2, &'d')]);
+        assert_eq!(slab.iter_since(slab.revision()).count(), 0);
+        assert_eq!(slab.iter_since(0).count(), 3);
+
+        let untracked = OchenSlab::from_iter_with_capacity(2, 0..2);
+        assert_eq!(untracked.iter_since(0).count(), 0);
+    }
+
+    #[test]
+    fn can_grow_into_spare_capacity() {
+        let mut storage = Vec::with_capacity(6);
+        storage.extend(vec![Some(1), None, Some(3)]);
+        let mut slab = OchenSlab::from_raw_parts(storage, vec![1]);
+        let ptr = slab.storage.as_ptr();
+        assert_eq!(slab.try_grow_into_spare(), 3);
+        assert_eq!(slab.capacity(), 6);
+        assert_eq!(slab.storage.as_ptr(), ptr);
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.try_grow_into_spare(), 0);
+        let inserted: Vec<_> = (0..4).map(|value| slab.insert(value).unwrap()).collect();
+        assert_eq!(inserted, [3, 4, 5, 1]);
+    }
+
+    #[test]
+    fn retain_compacting_packs_survivors() {
+        let mut slab = OchenSlab::with_capacity_ordered(8);
+        for value in 0..7 {
+            slab.insert(value);
+        }
+        slab.remove(1);
+        let moves = slab.retain_compacting(|index, value| {
+            *value *= 10;
+            index % 3 != 0
+        });
+        assert_eq!(moves, [(2, 0), (4, 1), (5, 2)]);
+        assert_eq!(slab.to_option_vec()[..4], [Some(20), Some(40), Some(50), None]);
+        assert!(slab.is_contiguous());
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.insert(0), Some(3));
+        assert_eq!(slab.evict_one(EvictPolicy::Oldest), Some((0, 20)));
+    }
+
+    #[test]
+    fn find_map_returns_first_value() {
+        let items = vec![(1, None), (2, Some('b')), (3, Some('c'))];
+        let mut slab = OchenSlab::from_iter_with_capacity(4, items);
+        slab.remove(1);
+        let name = slab.find_map(|index, (_, name)| name.map(|name| (index, name)));
+        assert_eq!(name, Some((2, 'c')));
+        assert_eq!(slab.find_map(|_, (id, _)| if *id > 5 { Some(*id) } else { None }), None);
+    }
+
+    #[test]
+    fn compare_and_replace_checks_expected() {
+        let mut slab = OchenSlab::from_iter_with_capacity(3, vec![String::from("a")]);
+        let revision = slab.revision();
+        assert_eq!(slab.compare_and_replace(0, &"b".into(), "c".into()), Err("c".into()));
+        assert_eq!(slab.revision(), revision);
+        assert_eq!(slab.compare_and_replace(1, &"a".into(), "c".into()), Err("c".into()));
+        assert_eq!(slab.compare_and_replace(7, &"a".into(), "c".into()), Err("c".into()));
+        assert_eq!(slab.compare_and_replace(0, &"a".into(), "c".into()), Ok("a".into()));
+        assert_eq!(slab.get(0).map(String::as_str), Some("c"));
+        assert_ne!(slab.revision(), revision);
+    }
+
+    #[test]
+    fn try_get_disjoint_mut_borrows_all() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 0..4);
+        let items = slab.try_get_disjoint_mut([3, 0, 2]);
+        let [a, b, c] = items.expect("try_get_disjoint_mut() failed");
+        std::mem::swap(a, b);
+        *c += 10;
+        assert_eq!(slab, &[Some(3), Some(1), Some(12), Some(0)][..]);
+    }
+
+    #[test]
+    fn try_get_disjoint_mut_names_offending_index() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 0..3);
+        slab.remove(1);
+        assert_eq!(slab.try_get_disjoint_mut([0, 5]).err(), Some(DisjointError::OutOfRange(5)));
+        assert_eq!(slab.try_get_disjoint_mut([2, 1]).err(), Some(DisjointError::Vacant(1)));
+        assert_eq!(slab.try_get_disjoint_mut([3, 0]).err(), Some(DisjointError::Vacant(3)));
+        assert_eq!(slab.try_get_disjoint_mut([0, 2, 0]).err(), Some(DisjointError::Duplicate(0)));
+        assert_eq!(slab.try_get_disjoint_mut([]).map(|items: [&mut i32; 0]| items.len()), Ok(0));
+    }
+
+    #[test]
+    fn split_at_rebases_high_half() {
+        let mut slab = OchenSlab::with_capacity_ordered(6);
+        for value in 0..6 {
+            slab.insert(value);
+        }
+        slab.remove(1);
+        slab.remove(4);
+        let (mut low, mut high) = slab.split_at(3);
+        assert_eq!(low, &[Some(0), None, Some(2)][..]);
+        assert_eq!(high, &[Some(3), None, Some(5)][..]);
+        assert_eq!(low.validate(), Ok(()));
+        assert_eq!(low.try_grow_into_spare(), 0);
+        assert_eq!(high.insert(7), Some(1));
+        assert_eq!(high.evict_one(EvictPolicy::Oldest), Some((0, 3)));
+
+        let (low, high) = OchenSlab::from_iter_with_capacity(2, 0..2).split_at(2);
+        assert_eq!((low.len(), high.capacity()), (2, 0));
+    }
+
+    #[test]
+    fn runs_describe_occupancy() {
+        let mut slab = OchenSlab::from_iter_with_capacity(6, 0..4);
+        slab.remove(1);
+        assert_eq!(slab.runs(), [(0, 1, true), (1, 1, false), (2, 2, true), (4, 2, false)]);
+        assert_eq!(OchenSlab::<u8>::with_capacity(3).runs(), [(0, 3, false)]);
+        assert_eq!(OchenSlab::<u8>::with_capacity(0).runs(), []);
+    }
+
+    #[test]
+    fn insert_all_is_all_or_nothing() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, vec!['a']);
+        assert_eq!(slab.insert_all(vec!['b', 'c', 'd', 'e']), Err(vec!['b', 'c', 'd', 'e']));
+        assert_eq!(slab.len(), 1);
+        assert_eq!(slab.insert_all(vec!['b', 'c']), Ok(vec![1, 2]));
+        assert_eq!(slab, &[Some('a'), Some('b'), Some('c'), None][..]);
+        assert_eq!(slab.insert_all(Vec::new()), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn disjoint_iter_mut_yields_each_item_once() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, 0..4);
+        slab.remove(2);
+        let mut items: Vec<_> = slab.disjoint_iter_mut(vec![3, 0, 3, 2, 9, 0, 1]).collect();
+        assert_eq!(items.iter().map(|item| **item).collect::<Vec<_>>(), [3, 0, 1]);
+        for item in items.iter_mut() {
+            **item += 10;
+        }
+        let first = items.swap_remove(0);
+        let second = items.swap_remove(0);
+        std::mem::swap(first, second);
+        assert_eq!(slab, &[Some(10), Some(13), None, Some(11), None][..]);
+    }
+
+    #[test]
+    fn try_from_raw_parts_checks_free_list() {
+        let slab = OchenSlab::try_from_raw_parts(vec![Some(1), None, None], vec![2, 1]);
+        let mut slab = slab.expect("try_from_raw_parts() failed");
+        assert_eq!(slab.insert(2), Some(1));
+
+        let missing = OchenSlab::try_from_raw_parts(vec![Some(1), None, None], vec![2]);
+        assert_eq!(missing.err(), Some(Corruption::VacantNotFree(1)));
+        let occupied = OchenSlab::try_from_raw_parts(vec![Some(1), None], vec![1, 0]);
+        assert_eq!(occupied.err(), Some(Corruption::FreeOccupied(0)));
+    }
+
+    #[test]
+    fn retain_indices_returns_removed_items() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, vec!['a', 'b', 'c', 'd']);
+        assert_eq!(slab.retain_indices(vec![3, 1, 4, 8]), [(0, 'a'), (2, 'c')]);
+        assert_eq!(slab, &[None, Some('b'), None, Some('d'), None][..]);
+        assert_eq!(slab.insert('x'), Some(2));
+        assert_eq!(slab.insert('y'), Some(0));
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn sample_occupancy_moves_toward_utilization() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, 0..4);
+        assert_eq!(slab.sample_occupancy(0.5), 0.5);
+        assert_eq!(slab.sample_occupancy(0.5), 0.75);
+        slab.remove(0);
+        slab.remove(1);
+        assert_eq!(slab.sample_occupancy(0.5), 0.625);
+        assert_eq!(slab.sample_occupancy(1.0), 0.5);
+        assert_eq!(slab.sample_occupancy(0.0), 0.5);
+    }
+
+    #[test]
+    fn drain_range_into_appends_to_buffer() {
+        let mut slab = OchenSlab::from_iter_with_capacity(8, 0..7);
+        slab.remove(2);
+        let mut out = Vec::with_capacity(8);
+        assert_eq!(slab.drain_range_into(1..4, &mut out), 2);
+        assert_eq!(slab.drain_range_into(5.., &mut out), 2);
+        assert_eq!(slab.drain_range_into(..=0, &mut out), 1);
+        assert_eq!(slab.drain_range_into(9..20, &mut out), 0);
+        assert_eq!(out, [(1, 1), (3, 3), (5, 5), (6, 6), (0, 0)]);
+        assert_eq!(slab, &[None, None, None, None, Some(4), None, None, None][..]);
+        assert_eq!(slab.validate(), Ok(()));
+    }
+
+    #[test]
+    fn can_convert_from_array() {
+        let mut slab = OchenSlab::from([10, 20, 30]);
+        assert_eq!(slab.capacity(), 3);
+        assert_eq!(slab.len(), 3);
+        assert_eq!(slab.get(1), Some(&20));
+        assert!(slab.insert(40).is_none());
+        assert!(OchenSlab::<u8>::from([]).is_empty());
+    }
+
+    #[test]
+    fn can_rotate_into_vacant_slots() {
+        let mut slab = OchenSlab::with_capacity(5);
+        slab.insert_all(vec!['a', 'b']).expect("insert_all() failed");
+        slab.remove(1);
+        slab.insert_all_at(vec![(3, 'd')]).expect("insert_all_at() failed");
+        assert_eq!(slab.rotate(6), Ok(vec![(0, 1), (3, 4)]));
+        assert_eq!(slab, &[None, Some('a'), None, None, Some('d')][..]);
+        assert_eq!(slab.rotate(10), Ok(vec![(1, 1), (4, 4)]));
+        assert_eq!(slab.insert('x'), Some(0));
+    }
+
+    #[test]
+    fn rotate_rejects_collisions() {
+        let mut slab = OchenSlab::from_iter_with_capacity(4, vec!['a', 'b']);
+        assert_eq!(slab.rotate(1), Err(RotateError { old: 0, new: 1 }));
+        assert_eq!(slab, &[Some('a'), Some('b'), None, None][..]);
+        assert_eq!(slab.rotate(2), Ok(vec![(0, 2), (1, 3)]));
+        assert_eq!(OchenSlab::<u8>::with_capacity(0).rotate(3), Ok(Vec::new()));
+    }
+
+    #[test]
+    #[ignore = "timing comparison, run with `cargo test --release -- --ignored --nocapture`"]
+    fn with_capacity_is_not_slower_than_resize_with() {
+        use std::time::{Duration, Instant};
+
+        const CAPACITY: usize = 1 << 22;
+        const ROUNDS: u32 = 8;
+
+        fn resize_with_parts(capacity: usize) -> (Vec<Option<String>>, Vec<usize>) {
+            let mut storage = Vec::<Option<String>>::with_capacity(capacity);
+            storage.resize_with(capacity, || None);
+            let mut free = Vec::<usize>::with_capacity(capacity);
+            let mut i = 0usize;
+            free.resize_with(capacity, || {
+                let value = capacity - 1 - i;
+                i += 1;
+                value
+            });
+            (storage, free)
+        }
+
+        let mut collected = Duration::MAX;
+        let mut resized = Duration::MAX;
+        for _ in 0..ROUNDS {
+            let start = Instant::now();
+            let slab = OchenSlab::<String>::with_capacity(CAPACITY);
+            collected = collected.min(start.elapsed());
+            assert_eq!(slab.free.len(), CAPACITY);
+
+            let start = Instant::now();
+            let (storage, free) = resize_with_parts(CAPACITY);
+            resized = resized.min(start.elapsed());
+            assert_eq!((storage.len(), free), (CAPACITY, slab.free));
+        }
+        println!("collect: {:?}, resize_with: {:?}", collected, resized);
+        assert!(collected <= resized * 2, "collect: {:?}, resize_with: {:?}", collected, resized);
     }
 }
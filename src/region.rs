@@ -0,0 +1,277 @@
+use std::ops::Range;
+
+use crate::OchenSlab;
+
+/// Part of an `OchenSlab` index space that can be used independently of other parts
+///
+/// Created by `OchenSlab::split_ranges`. All indices are global, so they remain valid for the
+/// parent slab afterwards. Regions of a `Send` item type can be handed to different threads.
+pub struct SlabRegion<'a, T> {
+    // Slots of this region
+    storage: &'a mut [Option<T>],
+
+    // Global index of the first slot
+    offset: usize,
+
+    // Free global indices within this region
+    free: Vec<usize>,
+
+    // Insertion revisions of slots of this region, empty if insertion order isn't tracked
+    inserted_at: &'a mut [u64],
+
+    // Revision recorded for items inserted through this region
+    revision: u64,
+}
+
+impl<'a, T> SlabRegion<'a, T> {
+    /// Return range of global indices covered by this region
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.storage.len()
+    }
+
+    /// Return number of slots in this region
+    pub fn capacity(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Return number of elements in this region
+    pub fn len(&self) -> usize {
+        self.storage.len() - self.free.len()
+    }
+
+    /// Return true if there are no elements in this region
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get reference to an item by its global index.
+    /// Returns None for indices outside of this region
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.storage.get(index.checked_sub(self.offset)?)?.as_ref()
+    }
+
+    /// Get mutable reference to an item by its global index.
+    /// Returns None for indices outside of this region
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.storage.get_mut(index.checked_sub(self.offset)?)?.as_mut()
+    }
+
+    /// Insert a new item into this region and return its global index.
+    /// Returns None if there's no space left in this region
+    /// All items inserted through regions are considered inserted at the same time, after
+    /// everything that was in the slab before the split.
+    pub fn insert(&mut self, t: T) -> Option<usize> {
+        let index = self.free.pop()?;
+        self.storage[index - self.offset] = Some(t);
+        if let Some(inserted_at) = self.inserted_at.get_mut(index - self.offset) {
+            *inserted_at = self.revision;
+        }
+        Some(index)
+    }
+
+    /// Remove an item by its global index.
+    /// Returns the item by value if there was one in this region
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let value = self.storage.get_mut(index.checked_sub(self.offset)?)?.take()?;
+        self.free.push(index);
+        Some(value)
+    }
+
+    /// Iterate over items of this region along with their global indices
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        let offset = self.offset;
+        self.storage.iter()
+            .enumerate()
+            .filter_map(move |(index, item)| Some((offset + index, item.as_ref()?)))
+    }
+}
+
+/// Mutable view of one half of an `OchenSlab` index space
+///
+/// Created by `OchenSlab::split_mut`. Both halves can be used at the same time, e.g. from
+/// different threads.
+pub struct HalfSlabMut<'a, T> {
+    // Slots of this half
+    storage: &'a mut [Option<T>],
+
+    // Global index of the first slot
+    offset: usize,
+}
+
+impl<'a, T> HalfSlabMut<'a, T> {
+    /// Return range of global indices covered by this half
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.storage.len()
+    }
+
+    /// Iterate over mutable items of this half along with their global indices
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
+        let offset = self.offset;
+        self.storage.iter_mut()
+            .enumerate()
+            .filter_map(move |(index, item)| Some((offset + index, item.as_mut()?)))
+    }
+}
+
+// Puts free indices of all regions back into the parent free list, even on panic. Regions are
+// appended highest first, so that an untouched split restores the original free list order
+struct Regions<'a, 'b, T> {
+    regions: Vec<SlabRegion<'a, T>>,
+    free: &'b mut Vec<usize>,
+    revision: &'b mut u64,
+}
+
+impl<'a, 'b, T> Drop for Regions<'a, 'b, T> {
+    fn drop(&mut self) {
+        for region in self.regions.iter_mut().rev() {
+            self.free.append(&mut region.free);
+        }
+        *self.revision = self.revision.wrapping_add(1);
+    }
+}
+
+impl<T> OchenSlab<T> {
+    /// Split index space at the midpoint into two halves that can be mutated independently.
+    /// Bumps revision if there are any items, as they may be changed through the halves
+    pub fn split_mut(&mut self) -> (HalfSlabMut<'_, T>, HalfSlabMut<'_, T>) {
+        if !self.is_empty() {
+            self.revision = self.revision.wrapping_add(1);
+        }
+        let middle = self.storage.len() / 2;
+        let (low, high) = self.storage.split_at_mut(middle);
+        (HalfSlabMut { storage: low, offset: 0 }, HalfSlabMut { storage: high, offset: middle })
+    }
+
+    /// Split index space into disjoint regions and run `f` with them.
+    /// `bounds` are ascending split points, so `[a, b]` produces regions `0..a`, `a..b` and
+    /// `b..capacity`. Each region gets free slots within its range only. Once `f` returns all
+    /// changes made through regions are visible in this slab, and revision is bumped.
+    /// Panics if `bounds` are not ascending or exceed capacity
+    pub fn split_ranges<R>(
+        &mut self,
+        bounds: &[usize],
+        f: impl FnOnce(&mut [SlabRegion<'_, T>]) -> R,
+    ) -> R {
+        let capacity = self.capacity();
+        let ascending = bounds.windows(2).all(|pair| pair[0] <= pair[1]);
+        assert!(ascending && bounds.iter().all(|end| *end <= capacity), "invalid region bounds");
+
+        let mut free = std::mem::take(&mut self.free);
+        let mut regions = Regions {
+            regions: Vec::with_capacity(bounds.len() + 1),
+            free: &mut self.free,
+            revision: &mut self.revision,
+        };
+
+        let revision = regions.revision.wrapping_add(1);
+        let mut storage = self.storage.as_mut_slice();
+        let mut inserted_at = self.inserted_at.as_mut_slice();
+        let mut offset = 0;
+        for end in bounds.iter().copied().chain(std::iter::once(capacity)) {
+            let (head, tail) = storage.split_at_mut(end - offset);
+            let (inserted_head, inserted_tail) = if inserted_at.is_empty() {
+                inserted_at.split_at_mut(0)
+            } else {
+                inserted_at.split_at_mut(end - offset)
+            };
+            let (region_free, rest) = free.into_iter().partition(|index| *index < end);
+            free = rest;
+            regions.regions.push(SlabRegion {
+                storage: head, offset, free: region_free, inserted_at: inserted_head, revision
+            });
+            storage = tail;
+            inserted_at = inserted_tail;
+            offset = end;
+        }
+
+        f(&mut regions.regions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_insert_within_their_ranges() {
+        let mut slab = OchenSlab::<usize>::with_capacity(8);
+        let existing = slab.insert(100).expect("insert() failed");
+        slab.split_ranges(&[2, 5], |regions| {
+            assert_eq!(regions.len(), 3);
+            assert_eq!(regions[0].range(), 0..2);
+            assert_eq!(regions[0].len(), 1);
+            assert_eq!(regions[0].get(existing), Some(&100));
+            assert_eq!(regions[1].get(existing), None);
+            for region in regions.iter_mut() {
+                let range = region.range();
+                while let Some(index) = region.insert(range.start) {
+                    assert!(range.contains(&index));
+                }
+                assert_eq!(region.len(), region.capacity());
+            }
+            assert_eq!(regions[2].capacity(), 3);
+            assert_eq!(regions[2].remove(6), Some(5));
+            assert_eq!(regions[1].remove(6), None);
+        });
+
+        assert_eq!(slab.len(), 7);
+        assert_eq!(slab.get(existing), Some(&100));
+        assert_eq!(slab.get(3), Some(&2));
+        assert!(slab.get(6).is_none());
+        assert_eq!(slab.insert(0), Some(6));
+        assert!(slab.insert(0).is_none());
+    }
+
+    #[test]
+    fn noop_split_keeps_insert_order() {
+        let mut slab = OchenSlab::<usize>::with_capacity(8);
+        slab.split_ranges(&[2, 5], |_| ());
+        let inserted: Vec<_> = (0..8).map(|value| slab.insert(value).unwrap()).collect();
+        assert_eq!(inserted, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn invalid_bounds_keep_free_list() {
+        let mut slab = OchenSlab::<usize>::with_capacity(4);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            slab.split_ranges(&[3, 1], |_| ());
+        }));
+        assert!(result.is_err());
+        assert_eq!(slab.validate(), Ok(()));
+        assert_eq!(slab.insert(0), Some(0));
+    }
+
+    #[test]
+    fn halves_can_be_mutated_from_threads() {
+        let mut slab = OchenSlab::from_iter_with_capacity(5, 0..5);
+        slab.remove(1);
+        {
+            let (mut low, mut high) = slab.split_mut();
+            assert_eq!(low.range(), 0..2);
+            assert_eq!(high.range(), 2..5);
+            std::thread::scope(|scope| {
+                scope.spawn(|| low.iter_mut().for_each(|(_, value)| *value += 10));
+                scope.spawn(|| high.iter_mut().for_each(|(_, value)| *value += 20));
+            });
+        }
+        assert_eq!(slab.to_option_vec(), [Some(10), None, Some(22), Some(23), Some(24)]);
+    }
+
+    #[test]
+    fn regions_can_be_used_from_threads() {
+        let mut slab = OchenSlab::<usize>::with_capacity(8);
+        slab.split_ranges(&[4], |regions| {
+            std::thread::scope(|scope| {
+                for region in regions.iter_mut() {
+                    scope.spawn(move || {
+                        let index = region.insert(1).expect("insert() failed");
+                        *region.get_mut(index).expect("get_mut() failed") += 1;
+                    });
+                }
+            });
+            assert_eq!(regions[0].iter().collect::<Vec<_>>(), [(0, &2)]);
+            assert_eq!(regions[1].iter().collect::<Vec<_>>(), [(4, &2)]);
+        });
+        assert_eq!(slab.len(), 2);
+    }
+}
@@ -0,0 +1,127 @@
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use crate::OchenSlab;
+
+impl<T> OchenSlab<T> {
+    /// Write this slab in a compact binary format: capacity as a LEB128 varint, then occupancy
+    /// bitmap with bit `i % 8` of byte `i / 8` set iff slot `i` is occupied, then items in
+    /// ascending index order, each written by `encode`.
+    /// Free list order and insertion order are not preserved
+    pub fn write_sparse<W, F>(&self, w: &mut W, mut encode: F) -> io::Result<()>
+    where
+        W: Write,
+        F: FnMut(&mut W, &T) -> io::Result<()>,
+    {
+        let mut capacity = self.capacity() as u64;
+        loop {
+            let byte = (capacity & 0x7f) as u8;
+            capacity >>= 7;
+            if capacity == 0 {
+                w.write_all(&[byte])?;
+                break;
+            }
+            w.write_all(&[byte | 0x80])?;
+        }
+
+        let mut bitmap = vec![0u8; self.capacity().div_ceil(8)];
+        for (index, _) in self.entries() {
+            bitmap[index / 8] |= 1 << (index % 8);
+        }
+        w.write_all(&bitmap)?;
+
+        for (_, item) in self.entries() {
+            encode(w, item)?;
+        }
+        Ok(())
+    }
+
+    /// Read a slab written by `write_sparse`, decoding every item with `decode`.
+    /// Items keep their indices, and vacant slots are reused in ascending index order
+    pub fn read_sparse<R, F>(r: &mut R, mut decode: F) -> io::Result<OchenSlab<T>>
+    where
+        R: Read,
+        F: FnMut(&mut R) -> io::Result<T>,
+    {
+        let mut capacity = 0u64;
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0u8];
+            r.read_exact(&mut byte)?;
+            if shift == 63 && byte[0] > 1 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "capacity is too large"));
+            }
+            capacity |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let capacity = usize::try_from(capacity)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "capacity is too large"))?;
+
+        // Read bitmap before allocating, so that corrupt capacity fails on missing data instead
+        let bitmap_len = capacity.div_ceil(8);
+        let mut bitmap = Vec::new();
+        r.take(bitmap_len as u64).read_to_end(&mut bitmap)?;
+        if bitmap.len() < bitmap_len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let mut storage = Vec::with_capacity(capacity);
+        for index in 0..capacity {
+            let item = if bitmap[index / 8] & 1 << (index % 8) != 0 {
+                Some(decode(r)?)
+            } else {
+                None
+            };
+            storage.push(item);
+        }
+        let mut slab = OchenSlab::from_raw_parts(storage, Vec::with_capacity(capacity));
+        slab.rebuild_free_list();
+        Ok(slab)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(w: &mut Vec<u8>, value: &u32) -> io::Result<()> {
+        w.write_all(&value.to_le_bytes())
+    }
+
+    fn decode(r: &mut &[u8]) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        r.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    #[test]
+    fn sparse_format_round_trips() {
+        let mut slab = OchenSlab::from_iter_with_capacity(200, 0..150u32);
+        slab.retain(|index, _| index % 7 == 3);
+        let mut bytes = Vec::new();
+        slab.write_sparse(&mut bytes, encode).expect("write_sparse() failed");
+        assert_eq!(bytes.len(), 2 + 25 + 4 * slab.len());
+
+        let mut restored = OchenSlab::read_sparse(&mut bytes.as_slice(), decode)
+            .expect("read_sparse() failed");
+        assert_eq!(restored, slab.to_option_vec().as_slice());
+        assert_eq!(restored.validate(), Ok(()));
+        assert_eq!(restored.insert(7), Some(0));
+    }
+
+    #[test]
+    fn read_sparse_rejects_truncated_input() {
+        let slab = OchenSlab::from_iter_with_capacity(10, 0..3u32);
+        let mut bytes = Vec::new();
+        slab.write_sparse(&mut bytes, encode).expect("write_sparse() failed");
+        for len in 0..bytes.len() {
+            let result = OchenSlab::read_sparse(&mut &bytes[..len], decode);
+            assert_eq!(result.err().map(|err| err.kind()), Some(io::ErrorKind::UnexpectedEof));
+        }
+
+        let huge = [0xff; 10];
+        let result = OchenSlab::read_sparse(&mut &huge[..], decode);
+        assert_eq!(result.err().map(|err| err.kind()), Some(io::ErrorKind::InvalidData));
+    }
+}
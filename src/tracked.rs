@@ -0,0 +1,143 @@
+use std::ops::Deref;
+
+use crate::OchenSlab;
+
+/// `OchenSlab` wrapper that records which slots were changed
+///
+/// Slots become dirty when an item is inserted into them or borrowed mutably, and removals are
+/// recorded separately, so that only changes need to be propagated elsewhere. Dereferences to the
+/// underlying slab for read-only access, which never marks anything dirty. Plain `OchenSlab`
+/// doesn't pay for any of this.
+///
+/// # Example
+/// ```
+/// use ochenslab::TrackedSlab;
+///
+/// let mut slab = TrackedSlab::<usize>::with_capacity(4);
+/// let a = slab.insert(1).unwrap();
+/// let b = slab.insert(2).unwrap();
+/// slab.clear_dirty();
+///
+/// *slab.get_mut(a).unwrap() = 3;
+/// slab.remove(b);
+///
+/// assert_eq!(slab.iter_dirty().collect::<Vec<_>>(), [(a, &3)]);
+/// assert_eq!(slab.removed_since_clear(), [b]);
+/// ```
+pub struct TrackedSlab<T> {
+    slab: OchenSlab<T>,
+
+    // Dirty flag for every slot
+    dirty: Vec<bool>,
+
+    // Indices removed since the last clear
+    removed: Vec<usize>,
+}
+
+impl<T> TrackedSlab<T> {
+    /// Create slab instance with given capacity and nothing marked dirty
+    pub fn with_capacity(capacity: usize) -> TrackedSlab<T> {
+        TrackedSlab {
+            slab: OchenSlab::with_capacity(capacity),
+            dirty: vec![false; capacity],
+            removed: Vec::new(),
+        }
+    }
+
+    /// Get mutable reference to an item by its index, marking it dirty
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let item = self.slab.get_mut(index)?;
+        self.dirty[index] = true;
+        Some(item)
+    }
+
+    /// Insert a new item, mark it dirty and return its index.
+    /// Returns None if there's no space left
+    pub fn insert(&mut self, t: T) -> Option<usize> {
+        let index = self.slab.insert(t)?;
+        self.dirty[index] = true;
+        Some(index)
+    }
+
+    /// Remove an item by its index, recording the removal.
+    /// Returns the item by value if there was one
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let value = self.slab.remove(index)?;
+        self.dirty[index] = false;
+        self.removed.push(index);
+        Some(value)
+    }
+
+    /// Iterate over dirty items along with their indices in ascending index order
+    pub fn iter_dirty(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.dirty.iter()
+            .enumerate()
+            .filter(|(_, dirty)| **dirty)
+            .filter_map(move |(index, _)| Some((index, self.slab.get(index)?)))
+    }
+
+    /// Return indices removed since the last `clear_dirty`, in removal order.
+    /// An index may also be dirty if a new item was inserted into it after removal
+    pub fn removed_since_clear(&self) -> &[usize] {
+        &self.removed
+    }
+
+    /// Forget all dirty flags and recorded removals, e.g. after synchronization
+    pub fn clear_dirty(&mut self) {
+        self.dirty.iter_mut().for_each(|dirty| *dirty = false);
+        self.removed.clear();
+    }
+
+    /// Unwrap the underlying slab, dropping tracking state
+    pub fn into_inner(self) -> OchenSlab<T> {
+        self.slab
+    }
+}
+
+impl<T> Deref for TrackedSlab<T> {
+    type Target = OchenSlab<T>;
+
+    fn deref(&self) -> &OchenSlab<T> {
+        &self.slab
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_mutated_items_are_dirty() {
+        let mut slab = TrackedSlab::<usize>::with_capacity(4);
+        let a = slab.insert(1).expect("insert() failed");
+        let b = slab.insert(2).expect("insert() failed");
+        let c = slab.insert(3).expect("insert() failed");
+        assert_eq!(slab.iter_dirty().count(), 3);
+        slab.clear_dirty();
+        assert_eq!(slab.iter_dirty().count(), 0);
+
+        assert_eq!(slab.get(a), Some(&1));
+        *slab.get_mut(b).expect("get_mut() failed") = 4;
+        let d = slab.insert(5).expect("insert() failed");
+        let dirty: Vec<_> = slab.iter_dirty().collect();
+        assert_eq!(dirty, [(b, &4), (d, &5)]);
+        assert!(slab.get(c).is_some());
+    }
+
+    #[test]
+    fn removals_are_reported_until_cleared() {
+        let mut slab = TrackedSlab::<usize>::with_capacity(4);
+        let a = slab.insert(1).expect("insert() failed");
+        let b = slab.insert(2).expect("insert() failed");
+        slab.remove(a);
+        slab.clear_dirty();
+        assert!(slab.removed_since_clear().is_empty());
+
+        assert_eq!(slab.remove(b), Some(2));
+        assert_eq!(slab.remove(b), None);
+        assert_eq!(slab.removed_since_clear(), [b]);
+        assert_eq!(slab.iter_dirty().count(), 0);
+        slab.clear_dirty();
+        assert!(slab.removed_since_clear().is_empty());
+    }
+}